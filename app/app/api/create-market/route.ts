@@ -6,9 +6,10 @@ import {
   MINT_SIZE,
   createInitializeMintInstruction,
   getMinimumBalanceForRentExemptMint,
-  createSetAuthorityInstruction,
-  AuthorityType,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
 } from "@solana/spl-token";
+import { createHash } from "crypto";
 import fs from "fs";
 import os from "os";
 import path from "path";
@@ -30,6 +31,11 @@ try {
   throw new Error("IDL file not found. Please run 'anchor build' first.");
 }
 
+// LMSR liquidity parameter for new markets, 1000 USDC
+const LMSR_B = 1_000_000_000;
+// Trading fee in basis points (1%), paid to the market authority
+const FEE_BPS = 100;
+
 // Local Wallet implementation
 class NodeWallet {
   constructor(readonly payer: Keypair) {}
@@ -127,132 +133,133 @@ export async function POST(request: Request) {
       usdcMint = new PublicKey("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
     }
 
-    // Create YES token mint
-    console.log("Creating YES token mint...");
-    const yesKeypair = Keypair.generate();
-    const mintRent = await getMinimumBalanceForRentExemptMint(connection);
-
-    const createYesTx = new anchor.web3.Transaction().add(
-      SystemProgram.createAccount({
-        fromPubkey: authority.publicKey,
-        newAccountPubkey: yesKeypair.publicKey,
-        space: MINT_SIZE,
-        lamports: mintRent,
-        programId: TOKEN_PROGRAM_ID,
-      }),
-      createInitializeMintInstruction(
-        yesKeypair.publicKey,
-        9, // Standard SPL token decimals
-        authority.publicKey,
-        null
-      )
+    // Derive Market PDA; the program creates the YES/NO mints from it
+    const [marketPda] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("market_v2"),
+        authority.publicKey.toBuffer(),
+        createHash("sha256").update(projectName).digest(),
+      ],
+      program.programId
     );
 
-    await provider.sendAndConfirm(createYesTx, [yesKeypair]);
-    console.log("YES mint created:", yesKeypair.publicKey.toString());
+    console.log("Market PDA:", marketPda.toString());
 
-    // Create NO token mint
-    console.log("Creating NO token mint...");
-    const noKeypair = Keypair.generate();
-    const createNoTx = new anchor.web3.Transaction().add(
-      SystemProgram.createAccount({
-        fromPubkey: authority.publicKey,
-        newAccountPubkey: noKeypair.publicKey,
-        space: MINT_SIZE,
-        lamports: mintRent,
-        programId: TOKEN_PROGRAM_ID,
-      }),
-      createInitializeMintInstruction(
-        noKeypair.publicKey,
-        9,
-        authority.publicKey,
-        null
+    // The program creates the outcome mints at these PDAs
+    const outcomeMintPdas = ["yes", "no"].map((seed) =>
+      PublicKey.findProgramAddressSync(
+        [Buffer.from("mint"), marketPda.toBuffer(), Buffer.from(seed)],
+        program.programId
       )
     );
+    const outcomeMints = outcomeMintPdas.map(([mint]) => mint);
 
-    await provider.sendAndConfirm(createNoTx, [noKeypair]);
-    console.log("NO mint created:", noKeypair.publicKey.toString());
+    // Make sure the program config exists
+    const [configPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("config")],
+      program.programId
+    );
+    let config = await (program.account as any)["programConfig"].fetchNullable(configPda);
+    if (!config) {
+      // Only the program's upgrade authority can create the config
+      const [programData] = PublicKey.findProgramAddressSync(
+        [program.programId.toBuffer()],
+        new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+      );
+      await program.methods
+        .initializeConfig(0, authority.publicKey)
+        .accountsPartial({
+          config: configPda,
+          admin: authority.publicKey,
+          program: program.programId,
+          programData,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      config = await (program.account as any)["programConfig"].fetch(configPda);
+    }
 
-    // Derive Market PDA
-    const [marketPda] = PublicKey.findProgramAddressSync(
-      [
-        Buffer.from("market_v2"),
-        authority.publicKey.toBuffer(),
-        Buffer.from(projectName),
-      ],
+    // The new market is appended to the registry page its id falls in
+    const page = Buffer.alloc(8);
+    page.writeUInt32LE(Math.floor(config.marketCount.toNumber() / 64), 0);
+    const [registryPage] = PublicKey.findProgramAddressSync(
+      [Buffer.from("registry"), page],
       program.programId
     );
 
-    console.log("Market PDA:", marketPda.toString());
+    // The authority funds the LMSR subsidy, b * ln 2 for a binary market
+    const authorityUsdcAccount = await getOrCreateAssociatedTokenAccount(
+      connection,
+      walletKeypair,
+      usdcMint,
+      authority.publicKey
+    );
+    if (isLocalnet) {
+      await mintTo(
+        connection,
+        walletKeypair,
+        usdcMint,
+        authorityUsdcAccount.address,
+        walletKeypair,
+        LMSR_B * 10
+      );
+    }
 
     // Initialize market
     const initTx = await program.methods
-      .initialize(
-        new anchor.BN(goal),
-        new anchor.BN(deadline),
-        projectName
-      )
+      .initialize({
+        fundraisingGoal: new anchor.BN(goal),
+        tradingCloseTs: new anchor.BN(deadline),
+        campaignEndTs: new anchor.BN(deadline),
+        settlementAvailableTs: new anchor.BN(deadline),
+        projectName,
+        lmsrB: new anchor.BN(LMSR_B),
+        feeBps: FEE_BPS,
+        feeRecipient: authority.publicKey,
+        lpFeeBps: 0,
+        mode: { lmsr: {} },
+        bucketBoundsBps: [],
+        scalarRange: null,
+        // The authority has a week after the deadline to settle before anyone can void
+        resolutionDeadline: new anchor.BN(deadline + 86400 * 7),
+        optimisticOracle: null,
+        fundraisingFeed: null,
+        campaignProgram: null,
+        outcomeMintBumps: outcomeMintPdas.map(([, bump]) => bump),
+      })
       .accounts({
         authority: authority.publicKey,
-        yesMint: yesKeypair.publicKey,
-        noMint: noKeypair.publicKey,
+        config: configPda,
+        registryPage,
+        authorityUsdcAccount: authorityUsdcAccount.address,
         usdcMint: usdcMint,
-        yesLiquidityAccount: PublicKey.findProgramAddressSync(
-          [Buffer.from("liquidity"), marketPda.toBuffer(), Buffer.from("yes")],
-          program.programId
-        )[0],
-        noLiquidityAccount: PublicKey.findProgramAddressSync(
-          [Buffer.from("liquidity"), marketPda.toBuffer(), Buffer.from("no")],
-          program.programId
-        )[0],
         usdcLiquidityAccount: PublicKey.findProgramAddressSync(
           [Buffer.from("liquidity"), marketPda.toBuffer(), Buffer.from("usdc")],
           program.programId
         )[0],
+        campaign: null,
       } as any)
+      .remainingAccounts(
+        outcomeMints.map((pubkey) => ({ pubkey, isSigner: false, isWritable: true }))
+      )
       .rpc();
 
     console.log("Market initialized! Transaction:", initTx);
 
-    // Transfer mint authority to Market PDA
-    console.log("Transferring mint authority to Market PDA...");
-
-    const transferYesAuthTx = new anchor.web3.Transaction().add(
-      createSetAuthorityInstruction(
-        yesKeypair.publicKey,
-        authority.publicKey,
-        AuthorityType.MintTokens,
-        marketPda
-      )
-    );
-    await provider.sendAndConfirm(transferYesAuthTx, [walletKeypair]);
-    console.log("Transferred YES mint authority");
-
-    const transferNoAuthTx = new anchor.web3.Transaction().add(
-      createSetAuthorityInstruction(
-        noKeypair.publicKey,
-        authority.publicKey,
-        AuthorityType.MintTokens,
-        marketPda
-      )
-    );
-    await provider.sendAndConfirm(transferNoAuthTx, [walletKeypair]);
-    console.log("Transferred NO mint authority");
-
     // Fetch market state
     const marketAccount = await (program.account as any)["marketState"].fetch(marketPda);
 
     return NextResponse.json({
       success: true,
       marketPda: marketPda.toString(),
-      yesMint: yesKeypair.publicKey.toString(),
-      noMint: noKeypair.publicKey.toString(),
+      yesMint: marketAccount.outcomeMints[0].toString(),
+      noMint: marketAccount.outcomeMints[1].toString(),
       usdcMint: usdcMint.toString(),
       transaction: initTx,
       marketState: {
         projectName: marketAccount.projectName?.toString(),
         fundraisingGoal: marketAccount.fundraisingGoal?.toString(),
-        deadline: marketAccount.deadline?.toString(),
+        tradingCloseTs: marketAccount.tradingCloseTs?.toString(),
       },
     });
   } catch (error: any) {
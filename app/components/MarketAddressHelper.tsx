@@ -16,12 +16,21 @@ export function MarketAddressHelper({
   const { publicKey } = useWallet();
   const program = useProgram();
   const [authorityAddress, setAuthorityAddress] = useState<string>("");
+  const [projectName, setProjectName] = useState<string>("");
   const [derivedPda, setDerivedPda] = useState<string>("");
 
-  const deriveMarketPda = (authority: PublicKey) => {
+  // Markets are keyed by authority and the SHA-256 hash of the project name
+  const deriveMarketPda = async (authority: PublicKey) => {
+    if (!projectName) {
+      alert("Please enter the project name");
+      return;
+    }
     try {
+      const nameSeed = Buffer.from(
+        await crypto.subtle.digest("SHA-256", new TextEncoder().encode(projectName))
+      );
       const [pda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("market"), authority.toBuffer()],
+        [Buffer.from("market_v2"), authority.toBuffer(), nameSeed],
         PROGRAM_ID
       );
       setDerivedPda(pda.toString());
@@ -58,6 +67,13 @@ export function MarketAddressHelper({
         💡 Quick Help: Get Market PDA
       </h3>
       <div className="space-y-2">
+        <input
+          type="text"
+          value={projectName}
+          onChange={(e) => setProjectName(e.target.value)}
+          placeholder="Project name..."
+          className="w-full text-xs px-2 py-1 border border-blue-300 dark:border-blue-700 rounded bg-white dark:bg-gray-700 text-gray-900 dark:text-white"
+        />
         {publicKey && (
           <div>
             <p className="text-xs text-blue-700 dark:text-blue-300 mb-2">
@@ -55,11 +55,12 @@ export function MarketCard({ market, onRemove, showRemove = false }: MarketCardP
               exists: true,
               projectName: marketState.projectName?.toString(),
               isSettled: marketState.isSettled,
-              deadline: marketState.deadline?.toNumber(),
+              deadline: marketState.tradingCloseTs?.toNumber(),
               fundraisingGoal: marketState.fundraisingGoal?.toNumber(),
-              yesLiquidity: marketState.yesLiquidity?.toNumber(),
-              noLiquidity: marketState.noLiquidity?.toNumber(),
+              yesLiquidity: marketState.outcomeQuantities?.[0]?.toNumber(),
+              noLiquidity: marketState.outcomeQuantities?.[1]?.toNumber(),
               usdcLiquidity: marketState.usdcLiquidity?.toNumber(),
+              lmsrB: marketState.lmsrB?.toNumber(),
             });
             
             // Update registry with project name if we got it
@@ -102,10 +103,14 @@ export function MarketCard({ market, onRemove, showRemove = false }: MarketCardP
     router.push(`/markets/${market.address}`);
   };
 
-  // Calculate probability if we have liquidity data
-  const yesProbability = marketData?.yesLiquidity && marketData?.noLiquidity && marketData?.usdcLiquidity
-    ? (marketData.yesLiquidity / (marketData.yesLiquidity + marketData.noLiquidity)) * 100
-    : null;
+  // LMSR price of YES if we have the market's quantities; parimutuel markets
+  // (b = 0) use YES's share of the stakes
+  const stakes = marketData ? marketData.yesLiquidity + marketData.noLiquidity : 0;
+  const yesProbability = marketData?.lmsrB
+    ? 100 / (1 + Math.exp((marketData.noLiquidity - marketData.yesLiquidity) / marketData.lmsrB))
+    : stakes > 0
+      ? (marketData.yesLiquidity / stakes) * 100
+      : null;
 
   return (
     <div
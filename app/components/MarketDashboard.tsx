@@ -23,6 +23,8 @@ export function MarketDashboard({ marketAddress }: MarketDashboardProps) {
   const [error, setError] = useState<string | null>(null);
   const [portfolioRefresh, setPortfolioRefresh] = useState(0);
   const [actualUsdcLiquidity, setActualUsdcLiquidity] = useState<number | null>(null);
+  // Outstanding YES / NO tokens, read from the mints
+  const [tokenSupplies, setTokenSupplies] = useState<[number, number]>([0, 0]);
 
   const marketPda = useMemo(() => {
     try {
@@ -126,6 +128,13 @@ export function MarketDashboard({ marketAddress }: MarketDashboardProps) {
           : Number(actualRawAmount);
 
         setActualUsdcLiquidity(actualRawNumber);
+
+        const [yesSupply, noSupply] = await Promise.all(
+          marketState.outcomeMints.slice(0, 2).map((mint: PublicKey) =>
+            connection.getTokenSupply(mint)
+          )
+        );
+        setTokenSupplies([Number(yesSupply.value.amount), Number(noSupply.value.amount)]);
       } catch (err) {
         // console.error("Error checking liquidity account:", err);
         setActualUsdcLiquidity(null);
@@ -193,7 +202,7 @@ export function MarketDashboard({ marketAddress }: MarketDashboardProps) {
   }
 
   // Calculate time remaining
-  const deadline = marketState.deadline.toNumber() * 1000;
+  const deadline = marketState.tradingCloseTs.toNumber() * 1000;
   const now = Date.now();
   const timeRemaining = deadline - now;
   const daysRemaining = Math.floor(timeRemaining / (1000 * 60 * 60 * 24));
@@ -201,12 +210,21 @@ export function MarketDashboard({ marketAddress }: MarketDashboardProps) {
     (timeRemaining % (1000 * 60 * 60 * 24)) / (1000 * 60 * 60)
   );
 
-  // Calculate implied probability
-  const yesLiquidity = marketState.yesLiquidity.toNumber();
-  const noLiquidity = marketState.noLiquidity.toNumber();
-  const totalLiquidity = yesLiquidity + noLiquidity;
+  // Outstanding YES / NO tokens
+  const [yesLiquidity, noLiquidity] = tokenSupplies;
+
+  // Implied probability is the LMSR price: exp(q_yes / b) / (exp(q_yes / b) + exp(q_no / b))
+  // The stored quantities are re-based, but only their difference matters
+  // Parimutuel markets have no b; use each side's share of the stakes instead
+  const qYes = marketState.outcomeQuantities[0].toNumber();
+  const qNo = marketState.outcomeQuantities[1].toNumber();
+  const b = marketState.lmsrB.toNumber();
   const yesProbability =
-    totalLiquidity > 0 ? (yesLiquidity / totalLiquidity) * 100 : 50;
+    b > 0
+      ? 100 / (1 + Math.exp((qNo - qYes) / b))
+      : qYes + qNo > 0
+        ? (qYes / (qYes + qNo)) * 100
+        : 50;
   const noProbability = 100 - yesProbability;
 
   return (
@@ -296,8 +314,8 @@ export function MarketDashboard({ marketAddress }: MarketDashboardProps) {
       {!marketState.isSettled && timeRemaining > 0 && (
         <TradingPanelEnhanced
           marketPda={marketPda!}
-          yesMint={marketState.yesMint}
-          noMint={marketState.noMint}
+          yesMint={marketState.outcomeMints[0]}
+          noMint={marketState.outcomeMints[1]}
           usdcMint={marketState.usdcMint}
           program={program}
           marketData={marketState}
@@ -313,8 +331,8 @@ export function MarketDashboard({ marketAddress }: MarketDashboardProps) {
         <UserPortfolio
           key={portfolioRefresh}
           userPublicKey={publicKey}
-          yesMint={marketState.yesMint}
-          noMint={marketState.noMint}
+          yesMint={marketState.outcomeMints[0]}
+          noMint={marketState.outcomeMints[1]}
           usdcMint={marketState.usdcMint}
           connection={connection}
           refreshTrigger={portfolioRefresh}
@@ -78,7 +78,7 @@ export function MarketStats({
           <div>
             <p className="text-sm font-bold text-yellow-200 uppercase tracking-wide">Market Settled</p>
             <p className="text-yellow-100">
-              {winningOutcome.yes !== undefined ? "YES" : "NO"} won!
+              {winningOutcome[0] === 0 ? "YES" : "NO"} won!
             </p>
           </div>
         </div>
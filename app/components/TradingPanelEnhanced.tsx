@@ -21,6 +21,9 @@ interface TradingPanelProps {
   onTransactionComplete?: () => void;
 }
 
+// The program's `Outcome(u8)` argument; outcome 0 is YES and 1 is NO
+const outcomeArg = (outcome: "yes" | "no") => ({ 0: outcome === "yes" ? 0 : 1 });
+
 export function TradingPanelEnhanced({
   marketPda,
   yesMint,
@@ -39,58 +42,49 @@ export function TradingPanelEnhanced({
   const [loading, setLoading] = useState(false);
   const [status, setStatus] = useState<string>("");
   const [preview, setPreview] = useState<string>("");
+  // Tokens (buy) or USDC (sell) the program quoted for the current input
+  const [quotedOut, setQuotedOut] = useState<BN | null>(null);
 
-  // Calculate price preview
+  // Ask the program for a quote; `quote` only returns data, so it is simulated
   useEffect(() => {
-    if (!amount || !marketData || parseFloat(amount) <= 0) {
+    setQuotedOut(null);
+    if (!amount || !program || !marketData || parseFloat(amount) <= 0) {
       setPreview("");
       return;
     }
 
-    try {
-      const amountNum = parseFloat(amount);
-      const yesLiq = marketData.yesLiquidity?.toNumber() || 0;
-      const noLiq = marketData.noLiquidity?.toNumber() || 0;
-      const usdcLiq = marketData.usdcLiquidity?.toNumber() || 0;
-
-      if (action === "buy") {
-        // Buying tokens with USDC
-        const amountUsdcLamports = amountNum * 1e6; // USDC has 6 decimals
-        const currentLiquidity = outcome === "yes" ? yesLiq : noLiq;
-
-        let tokensOut;
-        if (currentLiquidity === 0 || usdcLiq === 0) {
-          // Initial 1:1 ratio (but tokens have 9 decimals, USDC has 6)
-          tokensOut = amountUsdcLamports * 1000; // Convert 6 decimals to 9 decimals
+    let cancelled = false;
+    const amountNum = parseFloat(amount);
+    // Outcome tokens use the collateral's 6 decimals
+    const amountBN = new BN(Math.floor(amountNum * 1e6));
+    const side = action === "buy" ? { buy: {} } : { sell: {} };
+    (program.methods as any)
+      .quote(side, outcomeArg(outcome), amountBN)
+      .accountsPartial({ market: marketPda })
+      .view()
+      .then((quote: any) => {
+        if (cancelled) return;
+        const pricePerToken = quote.averagePrice.toNumber() / 1e6;
+        if (action === "buy") {
+          setQuotedOut(quote.tokensOut);
+          const tokensOutHuman = quote.tokensOut.toNumber() / 1e6;
+          setPreview(`You will receive ~${tokensOutHuman.toFixed(4)} ${outcome.toUpperCase()} tokens (~$${pricePerToken.toFixed(4)}/token)`);
         } else {
-          // AMM formula: tokens_out = (amount_usdc * current_liquidity) / (usdc_liquidity + amount_usdc)
-          tokensOut = (amountUsdcLamports * currentLiquidity) / (usdcLiq + amountUsdcLamports);
-        }
-
-        const tokensOutHuman = tokensOut / 1e9;
-        const pricePerToken = amountNum / tokensOutHuman;
-        setPreview(`You will receive ~${tokensOutHuman.toFixed(4)} ${outcome.toUpperCase()} tokens (~$${pricePerToken.toFixed(4)}/token)`);
-      } else {
-        // Selling tokens for USDC
-        const amountTokensLamports = amountNum * 1e9; // Tokens have 9 decimals
-        const currentLiquidity = outcome === "yes" ? yesLiq : noLiq;
-
-        if (currentLiquidity === 0 || usdcLiq === 0) {
-          setPreview("No liquidity available");
-          return;
+          setQuotedOut(quote.usdcOut);
+          const usdcOutHuman = quote.usdcOut.toNumber() / 1e6;
+          setPreview(`You will receive ~$${usdcOutHuman.toFixed(4)} USDC (~$${pricePerToken.toFixed(4)}/token)`);
         }
-
-        // AMM formula: usdc_out = (amount_tokens * usdc_liquidity) / (current_liquidity + amount_tokens)
-        const usdcOut = (amountTokensLamports * usdcLiq) / (currentLiquidity + amountTokensLamports);
-        const usdcOutHuman = usdcOut / 1e6;
-        const pricePerToken = usdcOutHuman / amountNum;
-        setPreview(`You will receive ~$${usdcOutHuman.toFixed(4)} USDC (~$${pricePerToken.toFixed(4)}/token)`);
-      }
-    } catch (err) {
-      console.error("Preview calculation error:", err);
-      setPreview("");
-    }
-  }, [amount, action, outcome, marketData]);
+      })
+      .catch((err: any) => {
+        if (cancelled) return;
+        console.error("Quote error:", err);
+        setPreview("");
+      });
+
+    return () => {
+      cancelled = true;
+    };
+  }, [amount, action, outcome, marketData, program, marketPda]);
 
   const handleTrade = async () => {
     if (!program || !publicKey || !amount || parseFloat(amount) <= 0) {
@@ -104,17 +98,7 @@ export function TradingPanelEnhanced({
 
       const amountNum = parseFloat(amount);
 
-      // Derive liquidity account PDAs
-      const [yesLiquidityAccount] = PublicKey.findProgramAddressSync(
-        [Buffer.from("liquidity"), marketPda.toBuffer(), Buffer.from("yes")],
-        program.programId
-      );
-
-      const [noLiquidityAccount] = PublicKey.findProgramAddressSync(
-        [Buffer.from("liquidity"), marketPda.toBuffer(), Buffer.from("no")],
-        program.programId
-      );
-
+      // The market's USDC vault
       const [usdcLiquidityAccount] = PublicKey.findProgramAddressSync(
         [Buffer.from("liquidity"), marketPda.toBuffer(), Buffer.from("usdc")],
         program.programId
@@ -128,7 +112,7 @@ export function TradingPanelEnhanced({
       const transaction = new Transaction();
 
       if (action === "buy") {
-        const amountBN = new BN(amountNum * 1e6); // USDC has 6 decimals
+        const amountBN = new BN(Math.floor(amountNum * 1e6)); // USDC has 6 decimals
 
         // Check USDC balance
         try {
@@ -164,71 +148,34 @@ export function TradingPanelEnhanced({
 
         setStatus("Creating buy instruction...");
 
-        const outcomeEnum = outcome === "yes" ? { yes: {} } : { no: {} };
         const accounts = {
           market: marketPda,
           user: publicKey,
-          yesMint,
-          noMint,
+          outcomeMint: userTokenMint,
           userTokenAccount,
           userUsdcAccount,
-          yesLiquidityAccount,
-          noLiquidityAccount,
           usdcLiquidityAccount,
           tokenProgram: TOKEN_PROGRAM_ID,
         };
 
-        // Try to build instruction using Anchor
-        let instruction;
-        try {
-          instruction = await (program.methods as any)
-            .buyTokens(amountBN, outcomeEnum)
-            .accounts(accounts)
-            .instruction();
-        } catch (err) {
-          // Fallback to manual encoding if needed
-          console.log("Using manual instruction encoding");
-          const outcomeU8 = outcome === "yes" ? 0 : 1;
-          const originalIdl = (program as any)._idl;
-          const buyTokensIx = originalIdl?.instructions.find((ix: any) => ix.name === "buy_tokens");
-
-          if (!buyTokensIx?.discriminator) throw new Error("Could not find instruction");
-
-          const discriminator = Buffer.from(buyTokensIx.discriminator);
-          const amountBuffer = Buffer.allocUnsafe(8);
-          amountBuffer.writeBigUInt64LE(BigInt(amountBN.toString()), 0);
-          const argsBuffer = Buffer.concat([amountBuffer, Buffer.from([outcomeU8])]);
-          const instructionData = Buffer.concat([discriminator, argsBuffer]);
-
-          instruction = {
-            keys: [
-              { pubkey: accounts.market, isSigner: false, isWritable: true },
-              { pubkey: accounts.user, isSigner: true, isWritable: true },
-              { pubkey: accounts.yesMint, isSigner: false, isWritable: true },
-              { pubkey: accounts.noMint, isSigner: false, isWritable: true },
-              { pubkey: accounts.userTokenAccount, isSigner: false, isWritable: true },
-              { pubkey: accounts.userUsdcAccount, isSigner: false, isWritable: true },
-              { pubkey: accounts.yesLiquidityAccount, isSigner: false, isWritable: true },
-              { pubkey: accounts.noLiquidityAccount, isSigner: false, isWritable: true },
-              { pubkey: accounts.usdcLiquidityAccount, isSigner: false, isWritable: true },
-              { pubkey: accounts.tokenProgram, isSigner: false, isWritable: false },
-            ],
-            programId: program.programId,
-            data: instructionData,
-          };
-        }
+        // Accept up to 1% less than quoted
+        const minOut = quotedOut ? quotedOut.muln(99).divn(100) : new BN(0);
+        const instruction = await (program.methods as any)
+          .buyTokens(amountBN, outcomeArg(outcome), minOut, null)
+          .accountsPartial(accounts)
+          .instruction();
 
         transaction.add(instruction);
       } else {
         // SELL
-        const amountTokensBN = new BN(amountNum * 1e9); // Tokens have 9 decimals
+        const amountTokensBN = new BN(Math.floor(amountNum * 1e6)); // Tokens use the collateral's 6 decimals
 
         // Check token balance
         try {
           const tokenAccountInfo = await getAccount(connection, userTokenAccount);
           const balance = new BN(tokenAccountInfo.amount.toString());
           if (balance.lt(amountTokensBN)) {
-            throw new Error(`Insufficient ${outcome.toUpperCase()} tokens. You have ${Number(tokenAccountInfo.amount) / 1e9}, need ${amountNum}`);
+            throw new Error(`Insufficient ${outcome.toUpperCase()} tokens. You have ${Number(tokenAccountInfo.amount) / 1e6}, need ${amountNum}`);
           }
         } catch (e: any) {
           if (e.name === "TokenAccountNotFoundError") {
@@ -257,57 +204,22 @@ export function TradingPanelEnhanced({
 
         setStatus("Creating sell instruction...");
 
-        const outcomeEnum = outcome === "yes" ? { yes: {} } : { no: {} };
         const accounts = {
           market: marketPda,
           user: publicKey,
-          yesMint,
-          noMint,
+          outcomeMint: userTokenMint,
           userTokenAccount,
           userUsdcAccount,
-          yesLiquidityAccount,
-          noLiquidityAccount,
           usdcLiquidityAccount,
           tokenProgram: TOKEN_PROGRAM_ID,
         };
 
-        let instruction;
-        try {
-          instruction = await (program.methods as any)
-            .sellTokens(amountTokensBN, outcomeEnum)
-            .accounts(accounts)
-            .instruction();
-        } catch (err) {
-          console.log("Using manual instruction encoding for sell");
-          const outcomeU8 = outcome === "yes" ? 0 : 1;
-          const originalIdl = (program as any)._idl;
-          const sellTokensIx = originalIdl?.instructions.find((ix: any) => ix.name === "sell_tokens");
-
-          if (!sellTokensIx?.discriminator) throw new Error("Could not find sell instruction");
-
-          const discriminator = Buffer.from(sellTokensIx.discriminator);
-          const amountBuffer = Buffer.allocUnsafe(8);
-          amountBuffer.writeBigUInt64LE(BigInt(amountTokensBN.toString()), 0);
-          const argsBuffer = Buffer.concat([amountBuffer, Buffer.from([outcomeU8])]);
-          const instructionData = Buffer.concat([discriminator, argsBuffer]);
-
-          instruction = {
-            keys: [
-              { pubkey: accounts.market, isSigner: false, isWritable: true },
-              { pubkey: accounts.user, isSigner: true, isWritable: true },
-              { pubkey: accounts.yesMint, isSigner: false, isWritable: true },
-              { pubkey: accounts.noMint, isSigner: false, isWritable: true },
-              { pubkey: accounts.userTokenAccount, isSigner: false, isWritable: true },
-              { pubkey: accounts.userUsdcAccount, isSigner: false, isWritable: true },
-              { pubkey: accounts.yesLiquidityAccount, isSigner: false, isWritable: true },
-              { pubkey: accounts.noLiquidityAccount, isSigner: false, isWritable: true },
-              { pubkey: accounts.usdcLiquidityAccount, isSigner: false, isWritable: true },
-              { pubkey: accounts.tokenProgram, isSigner: false, isWritable: false },
-            ],
-            programId: program.programId,
-            data: instructionData,
-          };
-        }
+        // Accept up to 1% less than quoted
+        const minOut = quotedOut ? quotedOut.muln(99).divn(100) : new BN(0);
+        const instruction = await (program.methods as any)
+          .sellTokens(amountTokensBN, outcomeArg(outcome), minOut, null)
+          .accountsPartial(accounts)
+          .instruction();
 
         transaction.add(instruction);
       }
@@ -355,6 +267,12 @@ export function TradingPanelEnhanced({
         errorMessage = "Market has already been settled. Trading is closed.";
       } else if (errorMessage.includes("DeadlinePassed")) {
         errorMessage = "Market deadline has passed. Trading is closed.";
+      } else if (errorMessage.includes("MarketPaused")) {
+        errorMessage = "Trading on this market is paused by its authority.";
+      } else if (errorMessage.includes("SellingDisabled")) {
+        errorMessage = "This is a parimutuel market. Stakes can't be sold before settlement.";
+      } else if (errorMessage.includes("SlippageExceeded")) {
+        errorMessage = "Price moved since the quote. Please try again.";
       }
 
       setStatus(`❌ Error: ${errorMessage}`);
@@ -454,11 +372,11 @@ export function TradingPanelEnhanced({
               <div className="flex justify-between items-center mb-1">
                 <span className="text-xs text-green-400 uppercase tracking-wider font-bold">If Outcome Occurs</span>
                 <span className="text-lg font-bold text-green-300 font-mono">
-                  +${(parseFloat(amount) * (outcome === 'yes' ? (100 / (marketData?.yesLiquidity ? (marketData.yesLiquidity.toNumber() / (marketData.yesLiquidity.toNumber() + marketData.noLiquidity.toNumber()) * 100) : 50)) : (100 / (100 - (marketData?.yesLiquidity ? (marketData.yesLiquidity.toNumber() / (marketData.yesLiquidity.toNumber() + marketData.noLiquidity.toNumber()) * 100) : 50))))).toFixed(2)}
+                  +${quotedOut ? (quotedOut.toNumber() / 1e6).toFixed(2) : "-"}
                 </span>
               </div>
               <p className="text-[10px] text-green-400/60 text-right">
-                Includes initial investment
+                Each winning token redeems for 1 USDC; includes initial investment
               </p>
             </div>
           )}
@@ -0,0 +1,45 @@
+use anchor_lang::prelude::*;
+
+/// `MarketState` as written by the original `market_v2` program, before any of
+/// the fields `migrate_legacy_market` converts it to
+/// It shares `MarketState`'s discriminator, so accounts are told apart by size
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct LegacyMarketState {
+    pub authority: Pubkey,
+    pub yes_mint: Pubkey,
+    pub no_mint: Pubkey,
+    pub usdc_mint: Pubkey,
+    pub fundraising_goal: u64,
+    /// Both the trading close and the earliest settlement time
+    pub deadline: i64,
+    /// Used as is in the market PDA's seeds, so at most 32 bytes
+    pub project_name: String,
+    pub yes_liquidity: u64,
+    pub no_liquidity: u64,
+    pub usdc_liquidity: u64,
+    pub is_settled: bool,
+    pub winning_outcome: Option<LegacyOutcome>,
+    pub bump: u8,
+}
+
+impl LegacyMarketState {
+    pub const LEN: usize = 32 + // authority
+        32 + // yes_mint
+        32 + // no_mint
+        32 + // usdc_mint
+        8 +  // fundraising_goal
+        8 +  // deadline
+        4 + 256 + // project_name (4 byte prefix + max 256 chars)
+        8 +  // yes_liquidity
+        8 +  // no_liquidity
+        8 +  // usdc_liquidity
+        1 +  // is_settled
+        1 + 1 + // winning_outcome (Option<LegacyOutcome>)
+        1; // bump
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum LegacyOutcome {
+    Yes,
+    No,
+}
@@ -1,109 +1,699 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_spl::associated_token::spl_associated_token_account::solana_program::hash::hash;
+use anchor_spl::associated_token::AssociatedToken;
 use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+use legacy::{LegacyMarketState, LegacyOutcome};
+
+pub mod legacy;
+pub mod lmsr;
 
 declare_id!("3p6L6xhYmiuHFqDvNZyJnvQ5d6c9Nhy5D673kDdsrW7h");
 
+/// Fixed-point scale for prices returned by `quote` (1.0 USDC per token == PRICE_SCALE)
+pub const PRICE_SCALE: u64 = 1_000_000;
+
+/// Basis points in 100%
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Fixed-point scale for `MarketState::payout_rate` (1 USDC per token == PAYOUT_SCALE)
+pub const PAYOUT_SCALE: u64 = 1_000_000_000;
+
+/// Longest project name a market can store, in bytes
+pub const MAX_PROJECT_NAME_LEN: usize = 256;
+
+/// Most outcomes a categorical market can have
+pub const MAX_OUTCOMES: usize = 8;
+
+/// Maximum members of a market's resolver committee
+pub const MAX_RESOLVERS: usize = 10;
+
+/// Maximum length of the evidence URI recorded at settlement
+pub const MAX_EVIDENCE_URI_LEN: usize = 200;
+
+/// How long a market migrated by `migrate_legacy_market` can be settled, from its
+/// deadline or the migration if later, before anyone can void it
+pub const LEGACY_RESOLUTION_WINDOW: i64 = 7 * 86_400;
+
+/// Market addresses held by each `MarketRegistryPage`
+pub const REGISTRY_PAGE_SIZE: usize = 64;
+
+/// Highest combined trading fee (recipient + LP) a market can charge (10%)
+pub const MAX_FEE_BPS: u16 = 1_000;
+
 #[program]
 pub mod indie_star_market {
     use super::*;
 
+    /// Create the program-wide config and market registry
+    /// Called once, by the program's upgrade authority, who becomes the config admin
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        protocol_fee_bps: u16,
+        protocol_fee_recipient: Pubkey,
+    ) -> Result<()> {
+        require!(protocol_fee_bps <= MAX_FEE_BPS, ErrorCode::InvalidFee);
+
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.admin.key();
+        config.market_count = 0;
+        config.protocol_fee_bps = protocol_fee_bps;
+        config.protocol_fee_recipient = protocol_fee_recipient;
+        config.bump = ctx.bumps.config;
+
+        msg!(
+            "Program config initialized | Admin: {} | Protocol fee: {} bps",
+            config.admin,
+            protocol_fee_bps
+        );
+
+        Ok(())
+    }
+
+    /// Update the protocol fee settings or hand the config to a new admin
+    /// Only callable by the config admin; a new rate applies to markets created after it
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        protocol_fee_bps: u16,
+        protocol_fee_recipient: Pubkey,
+        admin: Pubkey,
+    ) -> Result<()> {
+        require!(protocol_fee_bps <= MAX_FEE_BPS, ErrorCode::InvalidFee);
+
+        let config = &mut ctx.accounts.config;
+        config.protocol_fee_bps = protocol_fee_bps;
+        config.protocol_fee_recipient = protocol_fee_recipient;
+        config.admin = admin;
+
+        msg!(
+            "Program config updated | Admin: {} | Protocol fee: {} bps",
+            admin,
+            protocol_fee_bps
+        );
+
+        Ok(())
+    }
+
     /// Initialize a new prediction market for an Indie.fun project
-    /// Creates YES and NO token mints and sets up the initial market state
-    pub fn initialize(
-        ctx: Context<Initialize>,
-        fundraising_goal: u64,
-        deadline: i64,
-        project_name: String,
+    /// With no `bucket_bounds_bps` the market is binary (YES if the goal is met, else NO);
+    /// otherwise it is categorical, with one outcome per bucket of the result
+    /// A `scalar_range` makes it a scalar LONG/SHORT market over that range instead
+    /// Trading runs until `trading_close_ts`, no later than the campaign's end at
+    /// `campaign_end_ts`; settlement opens at `settlement_available_ts`, no earlier
+    /// The authority must settle before `resolution_deadline`; after it anyone can void
+    /// An `optimistic_oracle` also lets anyone settle through bonded proposals, and a
+    /// `fundraising_feed` lets anyone settle on the amount an oracle account reports
+    /// A `campaign_program` binds the market to the Indie.fun campaign account passed
+    /// as `campaign`, owned by that program; the goal and `campaign_end_ts` must match
+    /// the campaign's, and the market settles only on what the campaign reports
+    /// Creates one mint per outcome as a market-owned PDA (see `outcome_mint_seed`)
+    /// with the collateral's decimals, passed uninitialized in `remaining_accounts`
+    /// with their bumps in `outcome_mint_bumps`
+    /// The market takes the next sequential id and is appended to the current registry page
+    /// In LMSR mode the authority funds the worst-case subsidy (b * ln N) up front
+    /// and receives the first LP shares for it, one per USDC
+    /// Parimutuel markets need no subsidy; `lmsr_b` is ignored and `fee_bps` is the
+    /// house fee taken from the pot at settlement
+    /// The market charges the config's protocol fee rate as of its creation
+    pub fn initialize<'info>(
+        ctx: Context<'_, '_, '_, 'info, Initialize<'info>>,
+        params: InitializeParams,
     ) -> Result<()> {
-        let market = &mut ctx.accounts.market;
+        let InitializeParams {
+            fundraising_goal,
+            trading_close_ts,
+            campaign_end_ts,
+            settlement_available_ts,
+            project_name,
+            lmsr_b,
+            fee_bps,
+            fee_recipient,
+            lp_fee_bps,
+            mode,
+            bucket_bounds_bps,
+            scalar_range,
+            resolution_deadline,
+            optimistic_oracle,
+            fundraising_feed,
+            campaign_program,
+            outcome_mint_bumps,
+        } = params;
         let clock = Clock::get()?;
 
-        // Validate deadline is in the future
+        // Validate the trading, campaign and settlement windows line up
         require!(
-            deadline > clock.unix_timestamp,
+            trading_close_ts > clock.unix_timestamp,
             ErrorCode::InvalidDeadline
         );
+        require!(
+            trading_close_ts <= campaign_end_ts && campaign_end_ts <= settlement_available_ts,
+            ErrorCode::InvalidMarketSchedule
+        );
+        require!(
+            resolution_deadline > settlement_available_ts,
+            ErrorCode::InvalidResolutionDeadline
+        );
+        if let Some(oracle) = &optimistic_oracle {
+            require!(
+                oracle.bond > 0 && oracle.dispute_window > 0,
+                ErrorCode::InvalidOracleConfig
+            );
+        }
+        if let Some(feed) = &fundraising_feed {
+            require!(feed.max_staleness > 0, ErrorCode::InvalidFeedConfig);
+        }
+        let campaign = match campaign_program {
+            Some(program) => {
+                let account = ctx
+                    .accounts
+                    .campaign
+                    .as_ref()
+                    .ok_or(ErrorCode::CampaignMismatch)?;
+                let binding = CampaignBinding {
+                    campaign: account.key(),
+                    program,
+                };
+                let data = binding.read(account)?;
+                require!(
+                    data.goal == fundraising_goal,
+                    ErrorCode::CampaignGoalMismatch
+                );
+                require!(
+                    data.end_ts == campaign_end_ts,
+                    ErrorCode::CampaignEndMismatch
+                );
+                Some(binding)
+            }
+            None => None,
+        };
+        require!(
+            project_name.len() <= MAX_PROJECT_NAME_LEN,
+            ErrorCode::ProjectNameTooLong
+        );
+        require!(
+            fee_bps as u32 + lp_fee_bps as u32 <= MAX_FEE_BPS as u32,
+            ErrorCode::InvalidFee
+        );
+
+        // Bucket bounds are fractions of the goal and must be strictly increasing
+        require!(
+            bucket_bounds_bps.len() < MAX_OUTCOMES
+                && bucket_bounds_bps.windows(2).all(|pair| pair[0] < pair[1]),
+            ErrorCode::InvalidOutcomeBuckets
+        );
+        let (kind, outcome_count) = match scalar_range {
+            Some(range) => {
+                require!(
+                    bucket_bounds_bps.is_empty(),
+                    ErrorCode::InvalidOutcomeBuckets
+                );
+                require!(
+                    range.lower_bound < range.upper_bound,
+                    ErrorCode::InvalidScalarRange
+                );
+                (MarketKind::Scalar, 2)
+            }
+            None if bucket_bounds_bps.is_empty() => (MarketKind::Binary, 2),
+            None => (MarketKind::Categorical, bucket_bounds_bps.len() + 1),
+        };
+
+        let (lmsr_b, subsidy) = match mode {
+            MarketMode::Lmsr => {
+                require!(lmsr_b > 0, ErrorCode::InvalidLiquidityParameter);
+                (lmsr_b, lmsr::max_subsidy(lmsr_b, outcome_count)?)
+            }
+            MarketMode::Parimutuel => {
+                // No market maker, so nothing for LP fees to pay
+                require!(lp_fee_bps == 0, ErrorCode::InvalidFee);
+                (0, 0)
+            }
+        };
+
+        if subsidy > 0 {
+            // Fund the market maker's worst-case loss so every winning token stays backed
+            let transfer_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.authority_usdc_account.to_account_info(),
+                    to: ctx.accounts.usdc_liquidity_account.to_account_info(),
+                    authority: ctx.accounts.authority.to_account_info(),
+                },
+            );
+            token::transfer(transfer_ctx, subsidy)?;
+
+            // Mint the authority's LP shares for the subsidy
+            let authority_key = ctx.accounts.authority.key();
+            let name_seed = market_seed(&project_name);
+            let seeds = &[
+                b"market_v2".as_ref(),
+                authority_key.as_ref(),
+                name_seed.as_ref(),
+                &[ctx.bumps.market],
+            ];
+            let signer = &[&seeds[..]];
+            let mint_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::MintTo {
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    to: ctx.accounts.authority_lp_account.to_account_info(),
+                    authority: ctx.accounts.market.to_account_info(),
+                },
+                signer,
+            );
+            token::mint_to(mint_ctx, subsidy)?;
+        }
+
+        // Create one mint per outcome, owned by the market
+        let market_key = ctx.accounts.market.key();
+        require!(
+            ctx.remaining_accounts.len() == outcome_count
+                && outcome_mint_bumps.len() == outcome_count,
+            ErrorCode::InvalidOutcomeMint
+        );
+        let mut outcome_mints = [Pubkey::default(); MAX_OUTCOMES];
+        for (index, mint) in ctx.remaining_accounts.iter().enumerate() {
+            ctx.accounts.create_outcome_mint(
+                ctx.program_id,
+                mint,
+                kind,
+                index as u8,
+                outcome_mint_bumps[index],
+            )?;
+            outcome_mints[index] = mint.key();
+        }
+        let mut bounds = [0u32; MAX_OUTCOMES - 1];
+        bounds[..bucket_bounds_bps.len()].copy_from_slice(&bucket_bounds_bps);
+
+        // Register the market under the next sequential id
+        let config = &mut ctx.accounts.config;
+        let market_id = config.market_count;
+        config.market_count = market_id.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+
+        let registry_page = &mut ctx.accounts.registry_page;
+        registry_page.page = market_id / REGISTRY_PAGE_SIZE as u64;
+        registry_page.bump = ctx.bumps.registry_page;
+        registry_page.markets.push(market_key);
+
+        let market = &mut ctx.accounts.market;
 
         // Initialize market state
+        market.market_id = market_id;
         market.authority = ctx.accounts.authority.key();
-        market.yes_mint = ctx.accounts.yes_mint.key();
-        market.no_mint = ctx.accounts.no_mint.key();
+        market.kind = kind;
+        market.outcome_count = outcome_count as u8;
+        market.outcome_mints = outcome_mints;
+        market.bucket_bounds_bps = bounds;
+        market.scalar_range = scalar_range.unwrap_or_default();
         market.usdc_mint = ctx.accounts.usdc_mint.key();
         market.fundraising_goal = fundraising_goal;
-        market.deadline = deadline;
+        market.campaign_end_ts = campaign_end_ts;
+        market.trading_close_ts = trading_close_ts;
+        market.settlement_available_ts = settlement_available_ts;
+        market.resolution_deadline = resolution_deadline;
+        market.name_seed = market_seed(&project_name);
+        market.name_seed_len = 32;
         market.project_name = project_name;
-        market.yes_liquidity = 0;
-        market.no_liquidity = 0;
-        market.usdc_liquidity = 0;
+        market.outcome_quantities = [0; MAX_OUTCOMES];
+        market.outcome_supplies = [0; MAX_OUTCOMES];
+        market.usdc_liquidity = subsidy;
+        market.lmsr_b = lmsr_b;
+        market.fee_bps = fee_bps;
+        market.fee_recipient = fee_recipient;
+        market.accrued_fees = 0;
+        market.lp_mint = ctx.accounts.lp_mint.key();
+        market.lp_fee_bps = lp_fee_bps;
+        market.lp_fees = 0;
+        market.protocol_fee_bps = ctx.accounts.config.protocol_fee_bps;
+        market.protocol_fees = 0;
         market.is_settled = false;
+        market.is_voided = false;
         market.winning_outcome = None;
         market.bump = ctx.bumps.market;
+        market.payout_rates = [0; MAX_OUTCOMES];
+        market.mode = mode;
+        market.settlement = SettlementRecord::default();
+        market.optimistic_oracle = optimistic_oracle;
+        market.has_resolver_committee = false;
+        market.fundraising_feed = fundraising_feed;
+        market.campaign = campaign;
+        market.paused = false;
 
         msg!(
-            "Market initialized: {} | Goal: {} USDC | Deadline: {}",
+            "Market #{} initialized: {} | Goal: {} USDC | Trading closes: {} | Campaign ends: {} | Settles from: {} | {:?} {:?}, {} outcomes | b: {} | Subsidy: {}",
+            market_id,
             market.project_name,
             fundraising_goal,
-            deadline
+            trading_close_ts,
+            campaign_end_ts,
+            settlement_available_ts,
+            mode,
+            kind,
+            outcome_count,
+            lmsr_b,
+            subsidy
         );
 
         Ok(())
     }
 
-    /// Buy YES or NO tokens using USDC
-    /// Implements AMM pricing: x * y = k (constant product)
+    /// Buy outcome tokens using USDC
+    /// Priced by the LMSR cost function: the user pays C(q + x) - C(q) plus the trading fee
+    /// Parimutuel markets mint one token per USDC staked
+    /// `amount_usdc` includes the fee; fails if fewer than `min_tokens_out` tokens
+    /// would be minted or `expires_at` has passed
     pub fn buy_tokens(
         ctx: Context<BuyTokens>,
         amount_usdc: u64,
         outcome: Outcome,
-    ) -> Result<u64> {
+        min_tokens_out: u64,
+        expires_at: Option<i64>,
+    ) -> Result<TradeReceipt> {
         let clock = Clock::get()?;
-        
-        // Get account info before mutable borrow
-        let market_account_info = ctx.accounts.market.to_account_info();
-        
+        check_expiry(expires_at, &clock)?;
+
         // Read market state (immutable borrow)
         let market = &ctx.accounts.market;
-        
+
         // Check market is not settled
         require!(!market.is_settled, ErrorCode::MarketSettled);
-        require!(clock.unix_timestamp < market.deadline, ErrorCode::DeadlinePassed);
+        require!(!market.paused, ErrorCode::MarketPaused);
+        require!(
+            clock.unix_timestamp < market.trading_close_ts,
+            ErrorCode::DeadlinePassed
+        );
+        market.check_outcome(outcome)?;
 
-        // Extract values before mutable borrows
-        let bump = market.bump;
-        let authority = market.authority;
-        let project_name = market.project_name.clone();
-        let current_usdc_liquidity = market.usdc_liquidity;
-        let (mint, _liquidity_account, current_liquidity) = match outcome {
-            Outcome::Yes => (
-                &ctx.accounts.yes_mint,
-                &ctx.accounts.yes_liquidity_account,
-                market.yes_liquidity,
-            ),
-            Outcome::No => (
-                &ctx.accounts.no_mint,
-                &ctx.accounts.no_liquidity_account,
-                market.no_liquidity,
-            ),
+        // The fees come out of the amount paid; the rest buys tokens
+        let fees = TradeFees::on(amount_usdc, market)?;
+        let fee = fees.total()?;
+        let net_usdc = amount_usdc.checked_sub(fee).ok_or(ErrorCode::MathOverflow)?;
+
+        // Largest token amount whose LMSR cost fits in the net amount
+        let tokens_to_mint = match market.mode {
+            MarketMode::Lmsr => lmsr::tokens_for_collateral(
+                &market.quantities(),
+                market.lmsr_b,
+                outcome.index(),
+                net_usdc,
+            )?,
+            MarketMode::Parimutuel => net_usdc,
+        };
+        require!(tokens_to_mint > 0, ErrorCode::TradeTooSmall);
+        require!(
+            tokens_to_mint >= min_tokens_out,
+            ErrorCode::SlippageExceeded
+        );
+
+        ctx.accounts.execute(outcome, net_usdc, fees, tokens_to_mint)?;
+
+        msg!(
+            "Bought {} outcome {} tokens for {} USDC (fee {})",
+            tokens_to_mint,
+            outcome.index(),
+            amount_usdc,
+            fee
+        );
+
+        Ok(TradeReceipt {
+            tokens: tokens_to_mint,
+            usdc: amount_usdc,
+            fee,
+        })
+    }
+
+    /// Buy exactly `tokens_out` tokens of an outcome
+    /// Charges the LMSR cost rounded up plus the trading fee, failing if the total
+    /// exceeds `max_usdc_in`
+    pub fn buy_exact_tokens(
+        ctx: Context<BuyTokens>,
+        tokens_out: u64,
+        max_usdc_in: u64,
+        outcome: Outcome,
+    ) -> Result<TradeReceipt> {
+        let clock = Clock::get()?;
+        let market = &ctx.accounts.market;
+
+        require!(!market.is_settled, ErrorCode::MarketSettled);
+        require!(!market.paused, ErrorCode::MarketPaused);
+        require!(
+            clock.unix_timestamp < market.trading_close_ts,
+            ErrorCode::DeadlinePassed
+        );
+        market.check_outcome(outcome)?;
+        require!(tokens_out > 0, ErrorCode::TradeTooSmall);
+
+        let cost = match market.mode {
+            MarketMode::Lmsr => lmsr::cost_to_buy(
+                &market.quantities(),
+                market.lmsr_b,
+                outcome.index(),
+                tokens_out,
+            )?,
+            MarketMode::Parimutuel => tokens_out,
+        };
+        let fees = TradeFees::on(cost, market)?;
+        let fee = fees.total()?;
+        let usdc_in = cost.checked_add(fee).ok_or(ErrorCode::MathOverflow)?;
+        require!(usdc_in <= max_usdc_in, ErrorCode::SlippageExceeded);
+
+        ctx.accounts.execute(outcome, cost, fees, tokens_out)?;
+
+        msg!(
+            "Bought {} outcome {} tokens for {} USDC (fee {})",
+            tokens_out,
+            outcome.index(),
+            usdc_in,
+            fee
+        );
+
+        Ok(TradeReceipt {
+            tokens: tokens_out,
+            usdc: usdc_in,
+            fee,
+        })
+    }
+
+    /// Sell outcome tokens back for USDC
+    /// Priced by the LMSR cost function: the user receives C(q) - C(q - x) less the trading fee
+    /// Fails if less than `min_usdc_out` would be returned after fees or `expires_at` has passed
+    pub fn sell_tokens(
+        ctx: Context<SellTokens>,
+        amount_tokens: u64,
+        outcome: Outcome,
+        min_usdc_out: u64,
+        expires_at: Option<i64>,
+    ) -> Result<TradeReceipt> {
+        let clock = Clock::get()?;
+        check_expiry(expires_at, &clock)?;
+
+        // Read market state (immutable borrow)
+        let market = &ctx.accounts.market;
+
+        // Check market is not settled
+        require!(market.mode == MarketMode::Lmsr, ErrorCode::SellingDisabled);
+        require!(!market.is_settled, ErrorCode::MarketSettled);
+        require!(!market.paused, ErrorCode::MarketPaused);
+        require!(
+            clock.unix_timestamp < market.trading_close_ts,
+            ErrorCode::DeadlinePassed
+        );
+        market.check_outcome(outcome)?;
+
+        // Calculate USDC to return from the LMSR cost function
+        // Tokens from `split` can be sold too; the quantities are re-based as needed
+        let proceeds = lmsr::proceeds_from_sell(
+            &market.quantities(),
+            market.lmsr_b,
+            outcome.index(),
+            amount_tokens,
+        )?;
+        let fees = TradeFees::on(proceeds, market)?;
+        let fee = fees.total()?;
+        let usdc_to_return = proceeds.checked_sub(fee).ok_or(ErrorCode::MathOverflow)?;
+        require!(
+            usdc_to_return >= min_usdc_out,
+            ErrorCode::SlippageExceeded
+        );
+
+        ctx.accounts.execute(outcome, amount_tokens, proceeds, fees)?;
+
+        msg!(
+            "Sold {} outcome {} tokens for {} USDC (fee {})",
+            amount_tokens,
+            outcome.index(),
+            usdc_to_return,
+            fee
+        );
+
+        Ok(TradeReceipt {
+            tokens: amount_tokens,
+            usdc: usdc_to_return,
+            fee,
+        })
+    }
+
+    /// Sell just enough outcome tokens to receive exactly `usdc_out` after fees
+    /// The token amount is rounded up, failing if it exceeds `max_tokens_in`
+    pub fn sell_for_exact_usdc(
+        ctx: Context<SellTokens>,
+        usdc_out: u64,
+        max_tokens_in: u64,
+        outcome: Outcome,
+    ) -> Result<TradeReceipt> {
+        let clock = Clock::get()?;
+        let market = &ctx.accounts.market;
+
+        require!(market.mode == MarketMode::Lmsr, ErrorCode::SellingDisabled);
+        require!(!market.is_settled, ErrorCode::MarketSettled);
+        require!(!market.paused, ErrorCode::MarketPaused);
+        require!(
+            clock.unix_timestamp < market.trading_close_ts,
+            ErrorCode::DeadlinePassed
+        );
+        market.check_outcome(outcome)?;
+        require!(usdc_out > 0, ErrorCode::TradeTooSmall);
+
+        let proceeds = gross_for_net(usdc_out, market)?;
+        let fees = TradeFees::on(proceeds, market)?;
+        let fee = fees.total()?;
+        let tokens_in = lmsr::tokens_for_proceeds(
+            &market.quantities(),
+            market.lmsr_b,
+            outcome.index(),
+            proceeds,
+        )?;
+        require!(tokens_in <= max_tokens_in, ErrorCode::SlippageExceeded);
+
+        // The rounded-up token amount can pay slightly more than asked; the
+        // surplus stays in the pool so the user receives exactly `usdc_out`
+        ctx.accounts.execute(outcome, tokens_in, proceeds, fees)?;
+
+        msg!(
+            "Sold {} outcome {} tokens for {} USDC (fee {})",
+            tokens_in,
+            outcome.index(),
+            usdc_out,
+            fee
+        );
+
+        Ok(TradeReceipt {
+            tokens: tokens_in,
+            usdc: usdc_out,
+            fee,
+        })
+    }
+
+    /// Quote a buy or sell without executing it
+    /// Mutates nothing; the `Quote` is returned through `set_return_data`, so clients
+    /// can simulate this instruction instead of re-implementing the pricing
+    /// Buys take `amount` as USDC in, sells take it as tokens in
+    /// Parimutuel stakes always cost 1 USDC per token; their marginal price is the
+    /// outcome's share of all stakes once the trade is in
+    pub fn quote(
+        ctx: Context<GetQuote>,
+        side: TradeSide,
+        outcome: Outcome,
+        amount: u64,
+    ) -> Result<Quote> {
+        let clock = Clock::get()?;
+        let market = &ctx.accounts.market;
+
+        require!(!market.is_settled, ErrorCode::MarketSettled);
+        require!(
+            clock.unix_timestamp < market.trading_close_ts,
+            ErrorCode::DeadlinePassed
+        );
+        market.check_outcome(outcome)?;
+
+        let mut quantities = market.quantities();
+        let index = outcome.index();
+
+        if market.mode == MarketMode::Parimutuel {
+            require!(side == TradeSide::Buy, ErrorCode::SellingDisabled);
+            record_trade(market, &mut quantities, index, amount as i128)?;
+            let total: u128 = quantities.iter().map(|stake| *stake as u128).sum();
+            return Ok(Quote {
+                tokens_out: amount,
+                usdc_out: 0,
+                average_price: PRICE_SCALE,
+                marginal_price: (quantities[index] as u128 * PRICE_SCALE as u128)
+                    .checked_div(total)
+                    .unwrap_or(0) as u64,
+                price_impact_bps: 0,
+                fee: 0,
+            });
+        }
+
+        let price_before = lmsr::price(&quantities, market.lmsr_b, index)?;
+
+        // `usdc` is what the trader pays or receives, fee included
+        let (tokens, usdc, fee, tokens_out, usdc_out, delta) = match side {
+            TradeSide::Buy => {
+                let fee = TradeFees::on(amount, market)?.total()?;
+                let net = amount.checked_sub(fee).ok_or(ErrorCode::MathOverflow)?;
+                let tokens = lmsr::tokens_for_collateral(&quantities, market.lmsr_b, index, net)?;
+                (tokens, amount, fee, tokens, 0, tokens as i128)
+            }
+            TradeSide::Sell => {
+                let proceeds = lmsr::proceeds_from_sell(&quantities, market.lmsr_b, index, amount)?;
+                let fee = TradeFees::on(proceeds, market)?.total()?;
+                let usdc = proceeds.checked_sub(fee).ok_or(ErrorCode::MathOverflow)?;
+                (amount, usdc, fee, 0, usdc, -(amount as i128))
+            }
         };
 
-        // Calculate tokens to mint using AMM formula: x * y = k
-        // If no liquidity exists, use 1:1 ratio
-        let tokens_to_mint = if current_liquidity == 0 {
-            amount_usdc
+        lmsr::apply_trade(&mut quantities, index, delta)?;
+        let price_after = lmsr::price(&quantities, market.lmsr_b, index)?;
+
+        let average_price = if tokens == 0 {
+            0
         } else {
-            // AMM calculation: new_tokens = (amount_usdc * current_liquidity) / (usdc_liquidity + amount_usdc)
-            let numerator = amount_usdc
-                .checked_mul(current_liquidity)
-                .ok_or(ErrorCode::MathOverflow)?;
-            let denominator = current_usdc_liquidity
-                .checked_add(amount_usdc)
-                .ok_or(ErrorCode::MathOverflow)?;
-            numerator
-                .checked_div(denominator)
+            (usdc as u128)
+                .checked_mul(PRICE_SCALE as u128)
                 .ok_or(ErrorCode::MathOverflow)?
+                / tokens as u128
+        };
+        let price_before = price_before * PRICE_SCALE as u128 / lmsr::WAD;
+        let price_impact_bps = if tokens == 0 || price_before == 0 {
+            0
+        } else {
+            average_price.abs_diff(price_before) * BPS_DENOMINATOR as u128 / price_before
         };
 
-        // Transfer USDC from user to liquidity pool
+        Ok(Quote {
+            tokens_out,
+            usdc_out,
+            average_price: u64::try_from(average_price).map_err(|_| ErrorCode::MathOverflow)?,
+            marginal_price: (price_after * PRICE_SCALE as u128 / lmsr::WAD) as u64,
+            price_impact_bps: u64::try_from(price_impact_bps).map_err(|_| ErrorCode::MathOverflow)?,
+            fee,
+        })
+    }
+
+    /// Split USDC into complete sets
+    /// Deposits `amount` USDC into the collateral vault and mints `amount` of every outcome
+    /// `remaining_accounts` holds each outcome's mint followed by the user's token
+    /// account for it, in outcome order
+    pub fn split<'info>(
+        mut ctx: Context<'_, '_, 'info, 'info, Split<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        let market_account_info = ctx.accounts.market.to_account_info();
+        let market = &ctx.accounts.market;
+
+        require!(market.mode == MarketMode::Lmsr, ErrorCode::UnsupportedMarketMode);
+        require!(!market.is_settled, ErrorCode::MarketSettled);
+        require!(!market.paused, ErrorCode::MarketPaused);
+        require!(amount > 0, ErrorCode::TradeTooSmall);
+
+        let outcome_accounts =
+            complete_set_accounts(market, ctx.remaining_accounts, &ctx.accounts.user.key())?;
+
+        let bump = market.bump;
+        let authority = market.authority;
+        let name_seed = market.pda_name_seed().to_vec();
+
+        // Transfer USDC from user to the collateral vault
         let transfer_ctx = CpiContext::new(
             ctx.accounts.token_program.to_account_info(),
             Transfer {
@@ -112,140 +702,94 @@ pub mod indie_star_market {
                 authority: ctx.accounts.user.to_account_info(),
             },
         );
-        token::transfer(transfer_ctx, amount_usdc)?;
+        token::transfer(transfer_ctx, amount)?;
 
-        // Mint tokens to user
         let seeds = &[
             b"market_v2".as_ref(),
             authority.as_ref(),
-            project_name.as_bytes(),
+            name_seed.as_ref(),
             &[bump],
         ];
         let signer = &[&seeds[..]];
 
-        let mint_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            token::MintTo {
-                mint: mint.to_account_info(),
-                to: ctx.accounts.user_token_account.to_account_info(),
-                authority: market_account_info.clone(),
-            },
-            signer,
-        );
-        token::mint_to(mint_ctx, tokens_to_mint)?;
+        // Mint one token of every outcome per USDC deposited
+        for (mint, to) in outcome_accounts {
+            let mint_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::MintTo {
+                    mint,
+                    to,
+                    authority: market_account_info.clone(),
+                },
+                signer,
+            );
+            token::mint_to(mint_ctx, amount)?;
+        }
 
-        // Update liquidity (now we can mutably borrow)
         let market = &mut ctx.accounts.market;
-        match outcome {
-            Outcome::Yes => {
-                market.yes_liquidity = market.yes_liquidity
-                    .checked_add(tokens_to_mint)
-                    .ok_or(ErrorCode::MathOverflow)?;
-            }
-            Outcome::No => {
-                market.no_liquidity = market.no_liquidity
-                    .checked_add(tokens_to_mint)
-                    .ok_or(ErrorCode::MathOverflow)?;
-            }
-        }
         market.usdc_liquidity = market
             .usdc_liquidity
-            .checked_add(amount_usdc)
+            .checked_add(amount)
             .ok_or(ErrorCode::MathOverflow)?;
+        for index in 0..market.outcome_count as usize {
+            market.add_supply(index, amount as i128)?;
+        }
+
+        let accounts = &mut ctx.accounts;
+        assert_solvent(&accounts.market, &mut accounts.usdc_liquidity_account)?;
 
         msg!(
-            "Bought {} {} tokens for {} USDC",
-            tokens_to_mint,
-            match outcome {
-                Outcome::Yes => "YES",
-                Outcome::No => "NO",
-            },
-            amount_usdc
+            "Split {} USDC into {} of each of {} outcomes",
+            amount,
+            amount,
+            accounts.market.outcome_count
         );
 
-        Ok(tokens_to_mint)
+        Ok(())
     }
 
-    /// Sell YES or NO tokens back for USDC
-    /// Implements AMM pricing: x * y = k (constant product)
-    pub fn sell_tokens(
-        ctx: Context<SellTokens>,
-        amount_tokens: u64,
-        outcome: Outcome,
-    ) -> Result<u64> {
-        let clock = Clock::get()?;
-        
-        // Get account info before mutable borrow
+    /// Merge complete sets back into USDC
+    /// Burns `amount` of every outcome and returns `amount` USDC from the collateral vault
+    /// `remaining_accounts` is laid out as for `split`
+    pub fn merge<'info>(
+        mut ctx: Context<'_, '_, 'info, 'info, Merge<'info>>,
+        amount: u64,
+    ) -> Result<()> {
         let market_account_info = ctx.accounts.market.to_account_info();
-        
-        // Read market state (immutable borrow)
         let market = &ctx.accounts.market;
 
-        // Check market is not settled
-        require!(!market.is_settled, ErrorCode::MarketSettled);
-        require!(clock.unix_timestamp < market.deadline, ErrorCode::DeadlinePassed);
+        require!(market.mode == MarketMode::Lmsr, ErrorCode::UnsupportedMarketMode);
+        require!(amount > 0, ErrorCode::TradeTooSmall);
+
+        let outcome_accounts =
+            complete_set_accounts(market, ctx.remaining_accounts, &ctx.accounts.user.key())?;
 
-        // Extract values before mutable borrows
         let bump = market.bump;
         let authority = market.authority;
-        let project_name = market.project_name.clone();
-        let current_usdc_liquidity = market.usdc_liquidity;
-        let (mint, _liquidity_account, current_liquidity) = match outcome {
-            Outcome::Yes => (
-                &ctx.accounts.yes_mint,
-                &ctx.accounts.yes_liquidity_account,
-                market.yes_liquidity,
-            ),
-            Outcome::No => (
-                &ctx.accounts.no_mint,
-                &ctx.accounts.no_liquidity_account,
-                market.no_liquidity,
-            ),
-        };
-
-        // Validate sufficient liquidity
-        require!(
-            current_liquidity >= amount_tokens,
-            ErrorCode::InsufficientLiquidity
-        );
-
-        // Calculate USDC to return using AMM formula
-        let usdc_to_return = if current_usdc_liquidity == 0 {
-            0
-        } else {
-            // AMM calculation: usdc_out = (amount_tokens * usdc_liquidity) / (current_liquidity + amount_tokens)
-            let numerator = amount_tokens
-                .checked_mul(current_usdc_liquidity)
-                .ok_or(ErrorCode::MathOverflow)?;
-            let denominator = current_liquidity
-                .checked_add(amount_tokens)
-                .ok_or(ErrorCode::MathOverflow)?;
-            numerator
-                .checked_div(denominator)
-                .ok_or(ErrorCode::MathOverflow)?
-        };
+        let name_seed = market.pda_name_seed().to_vec();
 
-        // Burn tokens from user (user is the authority of their own token account)
-        let burn_ctx = CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            token::Burn {
-                mint: mint.to_account_info(),
-                from: ctx.accounts.user_token_account.to_account_info(),
-                authority: ctx.accounts.user.to_account_info(),
-            },
-        );
-        token::burn(burn_ctx, amount_tokens)?;
+        // Burn one token of every outcome per USDC returned
+        for (mint, from) in outcome_accounts {
+            let burn_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token::Burn {
+                    mint,
+                    from,
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            );
+            token::burn(burn_ctx, amount)?;
+        }
 
-        // Prepare signer seeds for USDC transfer from liquidity pool
         let seeds = &[
             b"market_v2".as_ref(),
             authority.as_ref(),
-            project_name.as_bytes(),
+            name_seed.as_ref(),
             &[bump],
         ];
         let signer = &[&seeds[..]];
 
-        // Transfer USDC from liquidity pool to user
+        // Transfer USDC from the collateral vault to user
         let transfer_ctx = CpiContext::new_with_signer(
             ctx.accounts.token_program.to_account_info(),
             Transfer {
@@ -255,240 +799,2484 @@ pub mod indie_star_market {
             },
             signer,
         );
-        token::transfer(transfer_ctx, usdc_to_return)?;
+        token::transfer(transfer_ctx, amount)?;
 
-        // Update liquidity (now we can mutably borrow)
         let market = &mut ctx.accounts.market;
-        match outcome {
-            Outcome::Yes => {
-                market.yes_liquidity = market.yes_liquidity
-                    .checked_sub(amount_tokens)
-                    .ok_or(ErrorCode::MathOverflow)?;
-            }
-            Outcome::No => {
-                market.no_liquidity = market.no_liquidity
-                    .checked_sub(amount_tokens)
-                    .ok_or(ErrorCode::MathOverflow)?;
-            }
-        }
         market.usdc_liquidity = market
             .usdc_liquidity
-            .checked_sub(usdc_to_return)
+            .checked_sub(amount)
             .ok_or(ErrorCode::MathOverflow)?;
+        for index in 0..market.outcome_count as usize {
+            market.add_supply(index, -(amount as i128))?;
+        }
+
+        let accounts = &mut ctx.accounts;
+        assert_solvent(&accounts.market, &mut accounts.usdc_liquidity_account)?;
 
         msg!(
-            "Sold {} {} tokens for {} USDC",
-            amount_tokens,
-            match outcome {
-                Outcome::Yes => "YES",
-                Outcome::No => "NO",
-            },
-            usdc_to_return
+            "Merged {} of each of {} outcomes into {} USDC",
+            amount,
+            accounts.market.outcome_count,
+            amount
         );
 
-        Ok(usdc_to_return)
+        Ok(())
     }
 
-    /// Settle the market after the deadline
-    /// Only callable by the market authority
-    /// Sets the winning outcome based on whether the fundraising goal was met
-    pub fn settle_market(
-        ctx: Context<SettleMarket>,
-        fundraising_result: u64,
-    ) -> Result<()> {
-        let market = &mut ctx.accounts.market;
+    /// Deposit USDC as market-making liquidity and receive LP shares
+    /// Scales b and the LMSR quantities by the same factor, so prices don't move
+    /// while the worst-case loss bound grows to cover the deposit
+    /// Shares are minted against the pool's expected value at current prices plus
+    /// its unclaimed LP fees, since `remove_liquidity` pays both out pro rata
+    pub fn add_liquidity(mut ctx: Context<AddLiquidity>, amount: u64) -> Result<u64> {
         let clock = Clock::get()?;
+        let market = &ctx.accounts.market;
 
-        // Check deadline has passed
+        require!(market.mode == MarketMode::Lmsr, ErrorCode::UnsupportedMarketMode);
+        require!(!market.is_settled, ErrorCode::MarketSettled);
+        require!(!market.paused, ErrorCode::MarketPaused);
         require!(
-            clock.unix_timestamp >= market.deadline,
-            ErrorCode::DeadlineNotPassed
+            clock.unix_timestamp < market.trading_close_ts,
+            ErrorCode::DeadlinePassed
         );
-        require!(!market.is_settled, ErrorCode::MarketAlreadySettled);
+        require!(amount > 1, ErrorCode::TradeTooSmall);
 
-        // Determine winning outcome
-        let winning_outcome = if fundraising_result >= market.fundraising_goal {
-            Outcome::Yes
-        } else {
-            Outcome::No
-        };
+        let value = pool_value(market, &market.supplies())?
+            .checked_add(market.lp_fees)
+            .ok_or(ErrorCode::MathOverflow)?;
+        require!(value > 0, ErrorCode::InsufficientLiquidity);
+        let shares = (amount as u128)
+            .checked_mul(ctx.accounts.lp_mint.supply as u128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / value as u128;
+        let shares = u64::try_from(shares).map_err(|_| ErrorCode::MathOverflow)?;
+        require!(shares > 0, ErrorCode::TradeTooSmall);
 
-        market.is_settled = true;
-        market.winning_outcome = Some(winning_outcome);
+        // The pool's remaining worst-case loss is C(q) - min(q) = C(q), since
+        // quantities are stored re-based to a zero minimum. Growing b by
+        // (amount - 1) / C(q) keeps that loss covered with a unit to spare.
+        let mut quantities = market.quantities();
+        let depth = lmsr::total_cost(&quantities, market.lmsr_b)?;
+        let added_b = (market.lmsr_b as u128)
+            .checked_mul((amount - 1) as u128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / depth as u128;
+        let new_b = market
+            .lmsr_b
+            .checked_add(u64::try_from(added_b).map_err(|_| ErrorCode::MathOverflow)?)
+            .ok_or(ErrorCode::MathOverflow)?;
+        lmsr::rescale(&mut quantities, market.lmsr_b, new_b)?;
+
+        let bump = market.bump;
+        let authority = market.authority;
+        let name_seed = market.pda_name_seed().to_vec();
+
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.user_usdc_account.to_account_info(),
+                to: ctx.accounts.usdc_liquidity_account.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
+            },
+        );
+        token::transfer(transfer_ctx, amount)?;
+
+        let seeds = &[
+            b"market_v2".as_ref(),
+            authority.as_ref(),
+            name_seed.as_ref(),
+            &[bump],
+        ];
+        let signer = &[&seeds[..]];
+        let mint_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            token::MintTo {
+                mint: ctx.accounts.lp_mint.to_account_info(),
+                to: ctx.accounts.user_lp_account.to_account_info(),
+                authority: ctx.accounts.market.to_account_info(),
+            },
+            signer,
+        );
+        token::mint_to(mint_ctx, shares)?;
+
+        let market = &mut ctx.accounts.market;
+        market.lmsr_b = new_b;
+        market.set_quantities(&quantities);
+        market.usdc_liquidity = market
+            .usdc_liquidity
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        let accounts = &mut ctx.accounts;
+        assert_solvent(&accounts.market, &mut accounts.usdc_liquidity_account)?;
 
         msg!(
-            "Market settled: {} | Goal: {} | Result: {} | Winner: {:?}",
-            market.project_name,
-            market.fundraising_goal,
-            fundraising_result,
-            winning_outcome
+            "Added {} USDC of liquidity for {} LP shares | b: {}",
+            amount,
+            shares,
+            new_b
         );
 
-        Ok(())
+        Ok(shares)
     }
 
-    /// Redeem winning tokens for USDC after market settlement
-    /// Only holders of winning outcome tokens can redeem 1:1 for USDC
-    pub fn redeem_tokens(ctx: Context<RedeemTokens>, amount: u64) -> Result<()> {
-        // Get account info before mutable operations
-        let market_account_info = ctx.accounts.market.to_account_info();
-        
-        // Read market state (immutable borrow)
+    /// Burn LP shares and withdraw the matching slice of the pool
+    /// Before settlement b and the quantities shrink pro rata and the LP receives its
+    /// share of the collateral left in the worst case, limited to what the pool can
+    /// release while still covering every outcome, plus its share of LP fees
+    /// After settlement the LP receives its share of the collateral left once every
+    /// winning token is paid, plus its share of LP fees
+    pub fn remove_liquidity(mut ctx: Context<RemoveLiquidity>, shares: u64) -> Result<u64> {
         let market = &ctx.accounts.market;
-
-        require!(market.is_settled, ErrorCode::MarketNotSettled);
+        let share_supply = ctx.accounts.lp_mint.supply;
         require!(
-            market.winning_outcome.is_some(),
-            ErrorCode::MarketNotSettled
+            shares > 0 && shares <= share_supply,
+            ErrorCode::InsufficientLiquidity
         );
 
-        let winning_outcome = market.winning_outcome.ok_or(ErrorCode::MarketNotSettled)?;
-        let (mint, _) = match winning_outcome {
-            Outcome::Yes => (&ctx.accounts.yes_mint, &ctx.accounts.no_mint),
-            Outcome::No => (&ctx.accounts.no_mint, &ctx.accounts.yes_mint),
+        let supplies = market.supplies();
+        let pro_rata = |amount: u64| -> Result<u64> {
+            let part = (amount as u128)
+                .checked_mul(shares as u128)
+                .ok_or(ErrorCode::MathOverflow)?
+                / share_supply as u128;
+            u64::try_from(part).map_err(|_| ErrorCode::MathOverflow.into())
         };
 
-        // Verify user is redeeming the correct token
-        require!(
-            ctx.accounts.user_token_account.mint == mint.key(),
-            ErrorCode::WrongTokenType
-        );
+        let mut quantities = market.quantities();
+        let mut new_b = market.lmsr_b;
+        let collateral_out = if market.is_settled {
+            let leftover = market
+                .usdc_liquidity
+                .saturating_sub(redeemable_liability(market, &supplies)?);
+            pro_rata(leftover)?
+        } else {
+            // The last shares can't leave an open market without a market maker
+            require!(shares < share_supply, ErrorCode::InsufficientLiquidity);
+            new_b = market.lmsr_b - pro_rata(market.lmsr_b)?;
+            require!(new_b > 0, ErrorCode::InsufficientLiquidity);
+            lmsr::rescale(&mut quantities, market.lmsr_b, new_b)?;
+
+            // Pro-rata share of what the pool keeps if the worst outcome wins,
+            // capped by what the shrunken market maker can actually release
+            let worst_case_equity = supplies
+                .iter()
+                .map(|supply| market.usdc_liquidity.saturating_sub(*supply))
+                .min()
+                .unwrap_or(0);
+            pro_rata(worst_case_equity)?.min(releasable_collateral(
+                market,
+                &quantities,
+                new_b,
+                &supplies,
+            )?)
+        };
+        let fees_out = pro_rata(market.lp_fees)?;
+        let amount = collateral_out
+            .checked_add(fees_out)
+            .ok_or(ErrorCode::MathOverflow)?;
 
-        // Extract values
         let bump = market.bump;
         let authority = market.authority;
-        let project_name = market.project_name.clone();
+        let name_seed = market.pda_name_seed().to_vec();
 
-        // Burn winning tokens (user is the authority of their own token account)
         let burn_ctx = CpiContext::new(
             ctx.accounts.token_program.to_account_info(),
             token::Burn {
-                mint: mint.to_account_info(),
-                from: ctx.accounts.user_token_account.to_account_info(),
+                mint: ctx.accounts.lp_mint.to_account_info(),
+                from: ctx.accounts.user_lp_account.to_account_info(),
                 authority: ctx.accounts.user.to_account_info(),
             },
         );
-        token::burn(burn_ctx, amount)?;
+        token::burn(burn_ctx, shares)?;
 
-        // Prepare signer seeds for USDC transfer from liquidity pool
         let seeds = &[
             b"market_v2".as_ref(),
             authority.as_ref(),
-            project_name.as_bytes(),
+            name_seed.as_ref(),
             &[bump],
         ];
         let signer = &[&seeds[..]];
-
-        // Transfer USDC 1:1 to user
         let transfer_ctx = CpiContext::new_with_signer(
             ctx.accounts.token_program.to_account_info(),
             Transfer {
                 from: ctx.accounts.usdc_liquidity_account.to_account_info(),
                 to: ctx.accounts.user_usdc_account.to_account_info(),
-                authority: market_account_info.clone(),
+                authority: ctx.accounts.market.to_account_info(),
+            },
+            signer,
+        );
+        token::transfer(transfer_ctx, amount)?;
+
+        let market = &mut ctx.accounts.market;
+        market.lmsr_b = new_b;
+        market.set_quantities(&quantities);
+        market.usdc_liquidity = market
+            .usdc_liquidity
+            .checked_sub(collateral_out)
+            .ok_or(ErrorCode::MathOverflow)?;
+        market.lp_fees = market
+            .lp_fees
+            .checked_sub(fees_out)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        let accounts = &mut ctx.accounts;
+        assert_solvent(&accounts.market, &mut accounts.usdc_liquidity_account)?;
+
+        msg!(
+            "Removed {} LP shares for {} USDC ({} in fees) | b: {}",
+            shares,
+            amount,
+            fees_out,
+            new_b
+        );
+
+        Ok(amount)
+    }
+
+    /// Transfer the fee recipient's accrued trading fees
+    /// Fees sit in the collateral vault but are never counted in `usdc_liquidity`;
+    /// the LP share of fees is paid out through `remove_liquidity`
+    pub fn claim_fees(ctx: Context<ClaimFees>) -> Result<u64> {
+        let market = &ctx.accounts.market;
+        let amount = market.accrued_fees;
+        require!(amount > 0, ErrorCode::NoFeesToClaim);
+
+        let bump = market.bump;
+        let authority = market.authority;
+        let name_seed = market.pda_name_seed().to_vec();
+        let seeds = &[
+            b"market_v2".as_ref(),
+            authority.as_ref(),
+            name_seed.as_ref(),
+            &[bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.usdc_liquidity_account.to_account_info(),
+                to: ctx.accounts.recipient_usdc_account.to_account_info(),
+                authority: ctx.accounts.market.to_account_info(),
             },
             signer,
         );
         token::transfer(transfer_ctx, amount)?;
 
-        msg!("Redeemed {} winning tokens for {} USDC", amount, amount);
+        ctx.accounts.market.accrued_fees = 0;
+
+        msg!("Claimed {} USDC in trading fees", amount);
+
+        Ok(amount)
+    }
+
+    /// Transfer a market's accrued protocol fees to the config's protocol fee recipient
+    pub fn claim_protocol_fees(ctx: Context<ClaimProtocolFees>) -> Result<u64> {
+        let amount = ctx.accounts.market.protocol_fees;
+        require!(amount > 0, ErrorCode::NoFeesToClaim);
+
+        transfer_from_market(
+            &ctx.accounts.market,
+            &ctx.accounts.usdc_liquidity_account,
+            &ctx.accounts.recipient_usdc_account,
+            &ctx.accounts.token_program,
+            amount,
+        )?;
+
+        ctx.accounts.market.protocol_fees = 0;
+
+        msg!("Claimed {} USDC in protocol fees", amount);
+
+        Ok(amount)
+    }
+
+    /// Settle the market once `settlement_available_ts` has passed
+    /// Only callable by the market authority, and only if it has no resolver committee
+    /// and isn't bound to a campaign, a fundraising feed or an optimistic oracle
+    /// Sets the winning outcome from `fundraising_result`: whether the goal was met for
+    /// binary markets, the bucket it falls in for categorical ones
+    /// Scalar markets have no single winner; LONG pays the fraction of the range the
+    /// result reached and SHORT the rest
+    /// Parimutuel markets also take the house fee from the pot here and fix the
+    /// payout per winning token at `pot / winning_stake`; if nobody staked on the
+    /// winner, every stake refunds at the void rate instead
+    /// The result, time, settler and evidence (a hash of the source data and a URI
+    /// to it) are kept in `market.settlement`
+    pub fn settle_market(
+        ctx: Context<SettleMarket>,
+        fundraising_result: u64,
+        evidence_hash: [u8; 32],
+        evidence_uri: String,
+    ) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+
+        // Check settlement is open
+        require!(
+            clock.unix_timestamp >= market.settlement_available_ts,
+            ErrorCode::DeadlineNotPassed
+        );
+        require!(!market.is_settled, ErrorCode::MarketAlreadySettled);
+        require!(!market.paused, ErrorCode::MarketPaused);
+        require!(
+            !market.has_resolver_committee,
+            ErrorCode::ResolverCommitteeRequired
+        );
+        require!(market.campaign.is_none(), ErrorCode::CampaignBound);
+        require!(market.fundraising_feed.is_none(), ErrorCode::FeedBound);
+        require!(market.optimistic_oracle.is_none(), ErrorCode::OracleBound);
+        require!(
+            clock.unix_timestamp < market.resolution_deadline,
+            ErrorCode::ResolutionDeadlinePassed
+        );
+        require!(
+            evidence_uri.len() <= MAX_EVIDENCE_URI_LEN,
+            ErrorCode::EvidenceUriTooLong
+        );
+
+        let collateral = vault_collateral(market, &ctx.accounts.usdc_liquidity_account)?;
+        settle(
+            market,
+            collateral,
+            SettlementRecord {
+                fundraising_result,
+                settled_at: clock.unix_timestamp,
+                settler: ctx.accounts.authority.key(),
+                evidence_hash,
+                evidence_uri,
+            },
+        )
+    }
+
+    /// Settle on the amount raised as reported by the market's fundraising feed
+    /// Callable by anyone; the feed account must be the configured one, owned by the
+    /// configured program, name the configured publisher and have been updated after
+    /// the campaign ended and within `max_staleness` seconds
+    /// The evidence hash is the hash of the feed account's data
+    pub fn settle_from_feed(ctx: Context<SettleFromFeed>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+        let config = market
+            .fundraising_feed
+            .ok_or(ErrorCode::FundraisingFeedDisabled)?;
+
+        require!(
+            clock.unix_timestamp >= market.settlement_available_ts,
+            ErrorCode::DeadlineNotPassed
+        );
+        require!(!market.is_settled, ErrorCode::MarketAlreadySettled);
+        require!(!market.paused, ErrorCode::MarketPaused);
+        require!(
+            !market.has_resolver_committee,
+            ErrorCode::ResolverCommitteeRequired
+        );
+        require!(market.campaign.is_none(), ErrorCode::CampaignBound);
+        require!(
+            clock.unix_timestamp < market.resolution_deadline,
+            ErrorCode::ResolutionDeadlinePassed
+        );
+
+        let feed = ctx.accounts.feed.to_account_info();
+        let reading = config.read(&feed)?;
+        require!(
+            reading.updated_at >= market.campaign_end_ts
+                && clock.unix_timestamp.saturating_sub(reading.updated_at) <= config.max_staleness,
+            ErrorCode::StaleFeed
+        );
+
+        let collateral = vault_collateral(market, &ctx.accounts.usdc_liquidity_account)?;
+        let evidence_hash = hash(&feed.try_borrow_data()?).to_bytes();
+        settle(
+            market,
+            collateral,
+            SettlementRecord {
+                fundraising_result: reading.value,
+                settled_at: clock.unix_timestamp,
+                settler: ctx.accounts.caller.key(),
+                evidence_hash,
+                evidence_uri: String::new(),
+            },
+        )
+    }
+
+    /// Settle a campaign-bound market on the campaign's `total_raised`
+    /// Callable by anyone once settlement is open and the campaign has
+    /// succeeded or failed; a cancelled campaign's market can only be voided
+    /// The evidence hash is the hash of the campaign account's data
+    pub fn settle_from_campaign(ctx: Context<SettleFromCampaign>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+        let binding = market.campaign.ok_or(ErrorCode::CampaignMismatch)?;
+
+        require!(
+            clock.unix_timestamp >= market.settlement_available_ts,
+            ErrorCode::DeadlineNotPassed
+        );
+        require!(!market.is_settled, ErrorCode::MarketAlreadySettled);
+        require!(!market.paused, ErrorCode::MarketPaused);
+        require!(
+            !market.has_resolver_committee,
+            ErrorCode::ResolverCommitteeRequired
+        );
+        require!(
+            clock.unix_timestamp < market.resolution_deadline,
+            ErrorCode::ResolutionDeadlinePassed
+        );
+
+        let campaign = ctx.accounts.campaign.to_account_info();
+        let data = binding.read(&campaign)?;
+        require!(
+            data.status != CampaignStatus::Cancelled,
+            ErrorCode::CampaignCancelled
+        );
+        require!(
+            matches!(
+                data.status,
+                CampaignStatus::Succeeded | CampaignStatus::Failed
+            ),
+            ErrorCode::CampaignNotFinished
+        );
+
+        let collateral = vault_collateral(market, &ctx.accounts.usdc_liquidity_account)?;
+        let evidence_hash = hash(&campaign.try_borrow_data()?).to_bytes();
+        settle(
+            market,
+            collateral,
+            SettlementRecord {
+                fundraising_result: data.total_raised,
+                settled_at: clock.unix_timestamp,
+                settler: ctx.accounts.caller.key(),
+                evidence_hash,
+                evidence_uri: String::new(),
+            },
+        )
+    }
+
+    /// Settle a binary market YES as soon as its trusted source reports the goal met,
+    /// even before trading closes; trading stops with it
+    /// `source` must be the market's bound campaign (not cancelled) or, for markets
+    /// without one, its fundraising feed (within `max_staleness`). Callable by anyone
+    pub fn early_settle(ctx: Context<EarlySettle>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+
+        require!(market.kind == MarketKind::Binary, ErrorCode::UnsupportedMarketKind);
+        require!(!market.is_settled, ErrorCode::MarketAlreadySettled);
+        require!(!market.paused, ErrorCode::MarketPaused);
+        require!(
+            !market.has_resolver_committee,
+            ErrorCode::ResolverCommitteeRequired
+        );
+        require!(
+            clock.unix_timestamp < market.resolution_deadline,
+            ErrorCode::ResolutionDeadlinePassed
+        );
+
+        let source = ctx.accounts.source.to_account_info();
+        let raised = match (market.campaign, market.fundraising_feed) {
+            (Some(binding), _) if source.key() == binding.campaign => {
+                let data = binding.read(&source)?;
+                require!(
+                    data.status != CampaignStatus::Cancelled,
+                    ErrorCode::CampaignCancelled
+                );
+                data.total_raised
+            }
+            (None, Some(config)) if source.key() == config.feed => {
+                let reading = config.read(&source)?;
+                require!(
+                    clock.unix_timestamp.saturating_sub(reading.updated_at) <= config.max_staleness,
+                    ErrorCode::StaleFeed
+                );
+                reading.value
+            }
+            _ => return err!(ErrorCode::UntrustedSource),
+        };
+        require!(raised >= market.fundraising_goal, ErrorCode::GoalNotReached);
+
+        let collateral = vault_collateral(market, &ctx.accounts.usdc_liquidity_account)?;
+        let evidence_hash = hash(&source.try_borrow_data()?).to_bytes();
+        settle(
+            market,
+            collateral,
+            SettlementRecord {
+                fundraising_result: raised,
+                settled_at: clock.unix_timestamp,
+                settler: ctx.accounts.caller.key(),
+                evidence_hash,
+                evidence_uri: String::new(),
+            },
+        )
+    }
+
+    /// Hand settlement to an M-of-N committee of `resolvers` (authority only, before
+    /// trading closes, and not for campaign-bound markets)
+    /// From then on every other settlement path is disabled and the market settles
+    /// once `threshold` resolvers submit results that pay out the same way
+    pub fn create_resolver_committee(
+        ctx: Context<CreateResolverCommittee>,
+        resolvers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+
+        require!(
+            clock.unix_timestamp < market.trading_close_ts,
+            ErrorCode::DeadlinePassed
+        );
+        require!(market.campaign.is_none(), ErrorCode::CampaignBound);
+        require!(
+            threshold > 0
+                && threshold as usize <= resolvers.len()
+                && resolvers.len() <= MAX_RESOLVERS,
+            ErrorCode::InvalidResolverCommittee
+        );
+        for (i, resolver) in resolvers.iter().enumerate() {
+            require!(
+                !resolvers[..i].contains(resolver),
+                ErrorCode::InvalidResolverCommittee
+            );
+        }
+
+        let committee = &mut ctx.accounts.committee;
+        committee.market = market.key();
+        committee.threshold = threshold;
+        committee.votes = vec![None; resolvers.len()];
+        committee.resolvers = resolvers;
+        committee.bump = ctx.bumps.committee;
+
+        market.has_resolver_committee = true;
+
+        msg!(
+            "Resolver committee created: {} | {} of {}",
+            market.project_name,
+            threshold,
+            committee.resolvers.len()
+        );
+
+        Ok(())
+    }
+
+    /// Vote on the fundraising result as a member of the market's resolver committee
+    /// Results that pay out the same way count as agreeing; the vote that brings one
+    /// to `threshold` settles the market on its result and evidence
+    /// Without consensus by `resolution_deadline` the market can be voided
+    pub fn submit_resolution(
+        ctx: Context<SubmitResolution>,
+        fundraising_result: u64,
+        evidence_hash: [u8; 32],
+        evidence_uri: String,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let market = &ctx.accounts.market;
+
+        require!(
+            clock.unix_timestamp >= market.settlement_available_ts,
+            ErrorCode::DeadlineNotPassed
+        );
+        require!(!market.is_settled, ErrorCode::MarketAlreadySettled);
+        require!(!market.paused, ErrorCode::MarketPaused);
+        require!(market.campaign.is_none(), ErrorCode::CampaignBound);
+        require!(
+            clock.unix_timestamp < market.resolution_deadline,
+            ErrorCode::ResolutionDeadlinePassed
+        );
+        require!(
+            evidence_uri.len() <= MAX_EVIDENCE_URI_LEN,
+            ErrorCode::EvidenceUriTooLong
+        );
+
+        let committee = &mut ctx.accounts.committee;
+        let resolver = ctx.accounts.resolver.key();
+        let seat = committee
+            .resolvers
+            .iter()
+            .position(|member| *member == resolver)
+            .ok_or(ErrorCode::Unauthorized)?;
+        require!(committee.votes[seat].is_none(), ErrorCode::AlreadyVoted);
+        committee.votes[seat] = Some(fundraising_result);
+
+        let weights = market.settlement_weights(fundraising_result)?;
+        let mut agreeing = 0usize;
+        for vote in committee.votes.iter().flatten() {
+            if market.settlement_weights(*vote)? == weights {
+                agreeing += 1;
+            }
+        }
+
+        msg!(
+            "Resolution submitted: {} | Result: {} | By: {} | {} of {} agree",
+            market.project_name,
+            fundraising_result,
+            resolver,
+            agreeing,
+            committee.threshold
+        );
+
+        if agreeing < committee.threshold as usize {
+            return Ok(());
+        }
+
+        let market = &mut ctx.accounts.market;
+        let collateral = vault_collateral(market, &ctx.accounts.usdc_liquidity_account)?;
+        settle(
+            market,
+            collateral,
+            SettlementRecord {
+                fundraising_result,
+                settled_at: clock.unix_timestamp,
+                settler: resolver,
+                evidence_hash,
+                evidence_uri,
+            },
+        )
+    }
+
+    /// Halt trading, splits, liquidity deposits and settlement on an unsettled market
+    /// (authority only)
+    /// Merges, withdrawals, fee claims and voiding keep working; the authority can void
+    /// a paused market before trading closes, and it redeems as usual
+    /// `reason` is an off-chain incident code carried in the `MarketPaused` event
+    pub fn pause_market(ctx: Context<SetMarketPaused>, reason: u16) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        require!(!market.is_settled, ErrorCode::MarketAlreadySettled);
+        require!(!market.paused, ErrorCode::MarketPaused);
+        market.paused = true;
+
+        emit!(MarketPaused {
+            market: market.key(),
+            reason,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Reopen a paused market (authority only)
+    pub fn resume_market(ctx: Context<SetMarketPaused>, reason: u16) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        require!(market.paused, ErrorCode::MarketNotPaused);
+        market.paused = false;
+
+        emit!(MarketResumed {
+            market: market.key(),
+            reason,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
         Ok(())
     }
+
+    /// Cancel an unsettled market and open refunds
+    /// The authority can void once trading has closed, or earlier while the market is
+    /// paused, unless a resolver committee decides the market; anyone can once
+    /// `resolution_deadline` has passed without a settlement
+    /// LMSR tokens refund at the last traded price, pro-rata if the vault is short;
+    /// parimutuel stakes refund from the whole pot with no house fee
+    pub fn void_market(ctx: Context<VoidMarket>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+
+        require!(!market.is_settled, ErrorCode::MarketAlreadySettled);
+        if ctx.accounts.caller.key() != market.authority || market.has_resolver_committee {
+            require!(
+                clock.unix_timestamp >= market.resolution_deadline,
+                ErrorCode::ResolutionDeadlineNotPassed
+            );
+        } else {
+            // Pausing first makes an early void visible to traders before it lands
+            require!(
+                clock.unix_timestamp >= market.trading_close_ts || market.paused,
+                ErrorCode::TradingStillOpen
+            );
+        }
+
+        let collateral = vault_collateral(market, &ctx.accounts.usdc_liquidity_account)?;
+        set_refund_rates(market, collateral)?;
+
+        // Voided markets count as settled so trading stops and redemption opens
+        market.is_settled = true;
+        market.is_voided = true;
+        market.winning_outcome = None;
+        market.settlement = SettlementRecord {
+            settled_at: clock.unix_timestamp,
+            settler: ctx.accounts.caller.key(),
+            ..Default::default()
+        };
+
+        msg!(
+            "Market voided: {} | By: {} | Refund rates: {:?}",
+            market.project_name,
+            ctx.accounts.caller.key(),
+            &market.payout_rates[..market.outcome_count as usize]
+        );
+
+        Ok(())
+    }
+
+    /// Propose the fundraising result for an optimistic-oracle market, posting the
+    /// market's bond in USDC
+    /// Anyone can propose once settlement is open, one proposal per market
+    /// Unless disputed within the dispute window it settles the market
+    pub fn propose_result(
+        ctx: Context<ProposeResult>,
+        fundraising_result: u64,
+        evidence_hash: [u8; 32],
+        evidence_uri: String,
+    ) -> Result<()> {
+        let market = &ctx.accounts.market;
+        let clock = Clock::get()?;
+        let oracle = market
+            .optimistic_oracle
+            .ok_or(ErrorCode::OptimisticOracleDisabled)?;
+
+        require!(
+            clock.unix_timestamp >= market.settlement_available_ts,
+            ErrorCode::DeadlineNotPassed
+        );
+        require!(!market.is_settled, ErrorCode::MarketAlreadySettled);
+        require!(!market.paused, ErrorCode::MarketPaused);
+        require!(
+            !market.has_resolver_committee,
+            ErrorCode::ResolverCommitteeRequired
+        );
+        require!(market.campaign.is_none(), ErrorCode::CampaignBound);
+        require!(
+            clock.unix_timestamp < market.resolution_deadline,
+            ErrorCode::ResolutionDeadlinePassed
+        );
+        require!(
+            evidence_uri.len() <= MAX_EVIDENCE_URI_LEN,
+            ErrorCode::EvidenceUriTooLong
+        );
+
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.proposer_usdc_account.to_account_info(),
+                to: ctx.accounts.bond_vault.to_account_info(),
+                authority: ctx.accounts.proposer.to_account_info(),
+            },
+        );
+        token::transfer(transfer_ctx, oracle.bond)?;
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.market = market.key();
+        proposal.proposer = ctx.accounts.proposer.key();
+        proposal.fundraising_result = fundraising_result;
+        proposal.evidence_hash = evidence_hash;
+        proposal.evidence_uri = evidence_uri;
+        proposal.proposed_at = clock.unix_timestamp;
+        proposal.proposer_bond = oracle.bond;
+        proposal.disputer = None;
+        proposal.disputer_bond = 0;
+        proposal.bump = ctx.bumps.proposal;
+
+        msg!(
+            "Result proposed: {} | Result: {} | By: {} | Bond: {}",
+            market.project_name,
+            fundraising_result,
+            proposal.proposer,
+            oracle.bond
+        );
+
+        Ok(())
+    }
+
+    /// Dispute the proposed result within the dispute window, posting a matching bond
+    /// The market's arbiter then decides the result and awards both bonds
+    pub fn dispute_result(ctx: Context<DisputeResult>) -> Result<()> {
+        let clock = Clock::get()?;
+        let proposal = &ctx.accounts.proposal;
+        let oracle = ctx
+            .accounts
+            .market
+            .optimistic_oracle
+            .ok_or(ErrorCode::OptimisticOracleDisabled)?;
+
+        require!(!ctx.accounts.market.is_settled, ErrorCode::MarketAlreadySettled);
+        require!(proposal.disputer.is_none(), ErrorCode::ProposalAlreadyDisputed);
+        require!(
+            clock.unix_timestamp < proposal.dispute_ends_at(&oracle)?,
+            ErrorCode::DisputeWindowClosed
+        );
+
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.disputer_usdc_account.to_account_info(),
+                to: ctx.accounts.bond_vault.to_account_info(),
+                authority: ctx.accounts.disputer.to_account_info(),
+            },
+        );
+        token::transfer(transfer_ctx, proposal.proposer_bond)?;
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.disputer = Some(ctx.accounts.disputer.key());
+        proposal.disputer_bond = proposal.proposer_bond;
+
+        msg!(
+            "Result disputed: {} | Proposed: {} | By: {}",
+            ctx.accounts.market.project_name,
+            proposal.fundraising_result,
+            ctx.accounts.disputer.key()
+        );
+
+        Ok(())
+    }
+
+    /// Settle on an undisputed proposal once its dispute window has closed and
+    /// return the proposer's bond
+    /// Callable by anyone
+    pub fn finalize_result(ctx: Context<FinalizeResult>) -> Result<()> {
+        let clock = Clock::get()?;
+        let proposal = &ctx.accounts.proposal;
+        let oracle = ctx
+            .accounts
+            .market
+            .optimistic_oracle
+            .ok_or(ErrorCode::OptimisticOracleDisabled)?;
+
+        require!(!ctx.accounts.market.is_settled, ErrorCode::MarketAlreadySettled);
+        require!(!ctx.accounts.market.paused, ErrorCode::MarketPaused);
+        require!(
+            !ctx.accounts.market.has_resolver_committee,
+            ErrorCode::ResolverCommitteeRequired
+        );
+        require!(proposal.disputer.is_none(), ErrorCode::ProposalAlreadyDisputed);
+        require!(
+            clock.unix_timestamp >= proposal.dispute_ends_at(&oracle)?,
+            ErrorCode::DisputeWindowOpen
+        );
+
+        transfer_from_market(
+            &ctx.accounts.market,
+            &ctx.accounts.bond_vault,
+            &ctx.accounts.proposer_usdc_account,
+            &ctx.accounts.token_program,
+            proposal.proposer_bond,
+        )?;
+
+        let record = SettlementRecord {
+            fundraising_result: proposal.fundraising_result,
+            settled_at: clock.unix_timestamp,
+            settler: proposal.proposer,
+            evidence_hash: proposal.evidence_hash,
+            evidence_uri: proposal.evidence_uri.clone(),
+        };
+        ctx.accounts.proposal.proposer_bond = 0;
+
+        let market = &mut ctx.accounts.market;
+        let collateral = vault_collateral(market, &ctx.accounts.usdc_liquidity_account)?;
+        settle(market, collateral, record)
+    }
+
+    /// Decide a disputed proposal (arbiter only) and settle the market on `fundraising_result`
+    /// The proposer wins both bonds if the result settles the market the same way as
+    /// the proposal, the disputer otherwise
+    pub fn resolve_dispute(
+        ctx: Context<ResolveDispute>,
+        fundraising_result: u64,
+        evidence_hash: [u8; 32],
+        evidence_uri: String,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let proposal = &ctx.accounts.proposal;
+
+        require!(!ctx.accounts.market.is_settled, ErrorCode::MarketAlreadySettled);
+        require!(!ctx.accounts.market.paused, ErrorCode::MarketPaused);
+        require!(
+            !ctx.accounts.market.has_resolver_committee,
+            ErrorCode::ResolverCommitteeRequired
+        );
+        require!(ctx.accounts.market.campaign.is_none(), ErrorCode::CampaignBound);
+        let disputer = proposal.disputer.ok_or(ErrorCode::ProposalNotDisputed)?;
+        require!(
+            evidence_uri.len() <= MAX_EVIDENCE_URI_LEN,
+            ErrorCode::EvidenceUriTooLong
+        );
+
+        // A proposal is right if it pays out the same, even when the raw amounts differ
+        let market = &ctx.accounts.market;
+        let upheld = market.settlement_weights(fundraising_result)?
+            == market.settlement_weights(proposal.fundraising_result)?;
+        let winner = if upheld {
+            proposal.proposer
+        } else {
+            disputer
+        };
+        require_keys_eq!(
+            ctx.accounts.winner_usdc_account.owner,
+            winner,
+            ErrorCode::TokenAccountOwnerMismatch
+        );
+        let award = proposal
+            .proposer_bond
+            .checked_add(proposal.disputer_bond)
+            .ok_or(ErrorCode::MathOverflow)?;
+        transfer_from_market(
+            &ctx.accounts.market,
+            &ctx.accounts.bond_vault,
+            &ctx.accounts.winner_usdc_account,
+            &ctx.accounts.token_program,
+            award,
+        )?;
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.proposer_bond = 0;
+        proposal.disputer_bond = 0;
+
+        msg!("Dispute resolved | Bonds of {} USDC awarded to {}", award, winner);
+
+        let market = &mut ctx.accounts.market;
+        let collateral = vault_collateral(market, &ctx.accounts.usdc_liquidity_account)?;
+        settle(
+            market,
+            collateral,
+            SettlementRecord {
+                fundraising_result,
+                settled_at: clock.unix_timestamp,
+                settler: ctx.accounts.arbiter.key(),
+                evidence_hash,
+                evidence_uri,
+            },
+        )
+    }
+
+    /// Take back a bond still held after the market was settled some other way or voided
+    pub fn reclaim_bond(ctx: Context<ReclaimBond>) -> Result<u64> {
+        require!(ctx.accounts.market.is_settled, ErrorCode::MarketNotSettled);
+
+        let claimant = ctx.accounts.claimant.key();
+        let proposal = &mut ctx.accounts.proposal;
+        let amount = if claimant == proposal.proposer && proposal.proposer_bond > 0 {
+            std::mem::take(&mut proposal.proposer_bond)
+        } else if proposal.disputer == Some(claimant) && proposal.disputer_bond > 0 {
+            std::mem::take(&mut proposal.disputer_bond)
+        } else {
+            return err!(ErrorCode::NoBondToReclaim);
+        };
+
+        transfer_from_market(
+            &ctx.accounts.market,
+            &ctx.accounts.bond_vault,
+            &ctx.accounts.claimant_usdc_account,
+            &ctx.accounts.token_program,
+            amount,
+        )?;
+
+        msg!("Reclaimed {} USDC bond", amount);
+
+        Ok(amount)
+    }
+
+    /// Grow a market account created by an older program version to the current
+    /// `MarketState::LEN`, with the payer covering the extra rent
+    /// Fields appended since then read as zero until set
+    /// Only layouts since `resize_market` was added can be grown; markets in the
+    /// original `market_v2` layout go through `migrate_legacy_market`
+    pub fn resize_market(ctx: Context<ResizeMarket>) -> Result<()> {
+        let market = &ctx.accounts.market;
+        require!(
+            market.try_borrow_data()?.starts_with(MarketState::DISCRIMINATOR),
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+        require!(
+            market.data_len() >= 8 + MarketState::MIN_RESIZABLE_LEN,
+            ErrorCode::UnsupportedMarketLayout
+        );
+
+        let new_len = 8 + MarketState::LEN;
+        if market.data_len() >= new_len {
+            return Ok(());
+        }
+
+        let rent = Rent::get()?.minimum_balance(new_len);
+        let top_up = rent.saturating_sub(market.lamports());
+        if top_up > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: market.to_account_info(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+        market.resize(new_len)?;
+
+        msg!("Market {} resized to {} bytes", market.key(), new_len);
+
+        Ok(())
+    }
+
+    /// Rewrite a market in the original `market_v2` layout as a current `MarketState`,
+    /// in place, with the payer covering the extra rent
+    /// The market keeps its address, and with it the raw project name as PDA seed,
+    /// and is appended to the registry under the next id
+    /// Its constant-product pricing has no equivalent here, so trading stays closed
+    /// and the market settles parimutuel: winners share the vault, and a void
+    /// refunds every token alike. It settles from `deadline` on and can be voided
+    /// by anyone `LEGACY_RESOLUTION_WINDOW` after that or the migration, if later
+    /// A market already settled keeps its winner, still paid 1:1 while the vault covers it
+    pub fn migrate_legacy_market(ctx: Context<MigrateLegacyMarket>) -> Result<()> {
+        let market = &ctx.accounts.market;
+        let legacy = {
+            let data = market.try_borrow_data()?;
+            require!(
+                data.starts_with(MarketState::DISCRIMINATOR),
+                ErrorCode::AccountDiscriminatorMismatch
+            );
+            require!(
+                data.len() == 8 + LegacyMarketState::LEN,
+                ErrorCode::InvalidLegacyMarket
+            );
+            LegacyMarketState::deserialize(&mut &data[8..])
+                .map_err(|_| error!(ErrorCode::InvalidLegacyMarket))?
+        };
+
+        // The old PDA used the name itself as seed, which also caps it at 32 bytes
+        let address = Pubkey::create_program_address(
+            &[
+                b"market_v2",
+                legacy.authority.as_ref(),
+                legacy.project_name.as_bytes(),
+                &[legacy.bump],
+            ],
+            ctx.program_id,
+        )
+        .map_err(|_| error!(ErrorCode::InvalidLegacyMarket))?;
+        require_keys_eq!(market.key(), address, ErrorCode::InvalidLegacyMarket);
+        require_keys_eq!(
+            ctx.accounts.yes_mint.key(),
+            legacy.yes_mint,
+            ErrorCode::OutcomeMintMismatch
+        );
+        require_keys_eq!(
+            ctx.accounts.no_mint.key(),
+            legacy.no_mint,
+            ErrorCode::OutcomeMintMismatch
+        );
+        require_keys_eq!(
+            ctx.accounts.usdc_liquidity_account.mint,
+            legacy.usdc_mint,
+            ErrorCode::CollateralMintMismatch
+        );
+
+        let clock = Clock::get()?;
+        let collateral = ctx.accounts.usdc_liquidity_account.amount;
+        let supplies = [ctx.accounts.yes_mint.supply, ctx.accounts.no_mint.supply];
+
+        let mut outcome_mints = [Pubkey::default(); MAX_OUTCOMES];
+        outcome_mints[..2].copy_from_slice(&[legacy.yes_mint, legacy.no_mint]);
+        let mut outcome_supplies = [0u64; MAX_OUTCOMES];
+        outcome_supplies[..2].copy_from_slice(&supplies);
+        let name_len = legacy.project_name.len();
+        let mut name_seed = [0u8; 32];
+        name_seed[..name_len].copy_from_slice(legacy.project_name.as_bytes());
+
+        let winning_outcome = legacy.winning_outcome.map(|outcome| match outcome {
+            LegacyOutcome::Yes => Outcome::YES,
+            LegacyOutcome::No => Outcome::NO,
+        });
+        let mut payout_rates = [0u128; MAX_OUTCOMES];
+        if let Some(winner) = winning_outcome {
+            let supply = supplies[winner.index()] as u128;
+            payout_rates[winner.index()] = (collateral as u128 * PAYOUT_SCALE as u128)
+                .checked_div(supply)
+                .unwrap_or(u128::MAX)
+                .min(PAYOUT_SCALE as u128);
+        }
+
+        // Register the market under the next sequential id
+        let config = &mut ctx.accounts.config;
+        let market_id = config.market_count;
+        config.market_count = market_id.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+
+        let registry_page = &mut ctx.accounts.registry_page;
+        registry_page.page = market_id / REGISTRY_PAGE_SIZE as u64;
+        registry_page.bump = ctx.bumps.registry_page;
+        registry_page.markets.push(market.key());
+
+        let state = MarketState {
+            market_id,
+            authority: legacy.authority,
+            kind: MarketKind::Binary,
+            outcome_count: 2,
+            outcome_mints,
+            bucket_bounds_bps: [0; MAX_OUTCOMES - 1],
+            scalar_range: ScalarRange::default(),
+            usdc_mint: legacy.usdc_mint,
+            fundraising_goal: legacy.fundraising_goal,
+            campaign_end_ts: legacy.deadline,
+            resolution_deadline: legacy
+                .deadline
+                .max(clock.unix_timestamp)
+                .checked_add(LEGACY_RESOLUTION_WINDOW)
+                .ok_or(ErrorCode::MathOverflow)?,
+            name_seed,
+            project_name: legacy.project_name,
+            outcome_quantities: outcome_supplies,
+            outcome_supplies,
+            usdc_liquidity: collateral,
+            lmsr_b: 0,
+            fee_bps: 0,
+            fee_recipient: legacy.authority,
+            accrued_fees: 0,
+            lp_mint: Pubkey::default(),
+            lp_fee_bps: 0,
+            lp_fees: 0,
+            is_settled: legacy.is_settled,
+            is_voided: false,
+            winning_outcome,
+            bump: legacy.bump,
+            payout_rates,
+            mode: MarketMode::Parimutuel,
+            settlement: SettlementRecord::default(),
+            optimistic_oracle: None,
+            has_resolver_committee: false,
+            fundraising_feed: None,
+            campaign: None,
+            trading_close_ts: legacy.deadline.min(clock.unix_timestamp),
+            settlement_available_ts: legacy.deadline,
+            paused: false,
+            protocol_fee_bps: 0,
+            protocol_fees: 0,
+            name_seed_len: name_len as u8,
+        };
+
+        let new_len = 8 + MarketState::LEN;
+        let rent = Rent::get()?.minimum_balance(new_len);
+        let top_up = rent.saturating_sub(market.lamports());
+        if top_up > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: market.to_account_info(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+        market.resize(new_len)?;
+        state.try_serialize(&mut &mut market.try_borrow_mut_data()?[..])?;
+
+        msg!(
+            "Legacy market migrated: {} | Id: {} | Collateral: {} | Supplies: {:?} | Winner: {:?}",
+            state.project_name,
+            market_id,
+            collateral,
+            supplies,
+            winning_outcome
+        );
+
+        Ok(())
+    }
+
+    /// Redeem winning tokens for USDC after market settlement
+    /// Only holders of winning outcome tokens can redeem, 1:1 for USDC unless the
+    /// vault was short at settlement, in which case at the recorded pro-rata rate
+    /// Parimutuel winners receive `stake * pot / winning_stake`, and every stake
+    /// its refund if nobody backed the winner
+    /// Scalar LONG and SHORT tokens both redeem, at their settled fractions, and
+    /// every outcome redeems at its refund rate once a market is voided
+    pub fn redeem_tokens(mut ctx: Context<RedeemTokens>, amount: u64) -> Result<()> {
+        // Get account info before mutable operations
+        let market_account_info = ctx.accounts.market.to_account_info();
+        
+        // Read market state (immutable borrow)
+        let market = &ctx.accounts.market;
+
+        require!(market.is_settled, ErrorCode::MarketNotSettled);
+
+        // Verify user is redeeming a token that pays out
+        let index = market
+            .outcome_of_mint(&ctx.accounts.outcome_mint.key())
+            .ok_or(ErrorCode::WrongTokenType)?;
+        let payout_rate = market.payout_rates[index];
+        require!(payout_rate > 0, ErrorCode::WrongTokenType);
+
+        // Extract values
+        let bump = market.bump;
+        let authority = market.authority;
+        let name_seed = market.pda_name_seed().to_vec();
+
+        // Burn winning tokens (user is the authority of their own token account)
+        let burn_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            token::Burn {
+                mint: ctx.accounts.outcome_mint.to_account_info(),
+                from: ctx.accounts.user_token_account.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
+            },
+        );
+        token::burn(burn_ctx, amount)?;
+
+        // Prepare signer seeds for USDC transfer from liquidity pool
+        let seeds = &[
+            b"market_v2".as_ref(),
+            authority.as_ref(),
+            name_seed.as_ref(),
+            &[bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        // Transfer USDC to user at the settlement payout rate
+        let payout = u64::try_from(
+            (amount as u128)
+                .checked_mul(payout_rate)
+                .ok_or(ErrorCode::MathOverflow)?
+                / PAYOUT_SCALE as u128,
+        )
+        .map_err(|_| ErrorCode::MathOverflow)?;
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.usdc_liquidity_account.to_account_info(),
+                to: ctx.accounts.user_usdc_account.to_account_info(),
+                authority: market_account_info.clone(),
+            },
+            signer,
+        );
+        token::transfer(transfer_ctx, payout)?;
+
+        let market = &mut ctx.accounts.market;
+        market.usdc_liquidity = market
+            .usdc_liquidity
+            .checked_sub(payout)
+            .ok_or(ErrorCode::MathOverflow)?;
+        market.add_supply(index, -(amount as i128))?;
+
+        let accounts = &mut ctx.accounts;
+        assert_solvent(&accounts.market, &mut accounts.usdc_liquidity_account)?;
+
+        msg!(
+            "Redeemed {} outcome {} tokens for {} USDC",
+            amount,
+            index,
+            payout
+        );
+
+        Ok(())
+    }
+}
+
+/// Fixed-size PDA seed for a project name: its SHA-256 hash
+/// Seeds are capped at 32 bytes, so the name itself can't be used for long names
+pub fn market_seed(project_name: &str) -> [u8; 32] {
+    hash(project_name.as_bytes()).to_bytes()
+}
+
+/// Last seed of outcome `index`'s mint PDA `[b"mint", market, seed]`
+/// Binary markets keep the `b"yes"` / `b"no"` mints; other kinds use `[index]`
+pub fn outcome_mint_seed(kind: MarketKind, index: u8) -> Vec<u8> {
+    match (kind, Outcome(index)) {
+        (MarketKind::Binary, Outcome::YES) => b"yes".to_vec(),
+        (MarketKind::Binary, Outcome::NO) => b"no".to_vec(),
+        _ => vec![index],
+    }
+}
+
+/// Trading fee on `amount` at `fee_bps`, rounded up in the pool's favor
+fn trading_fee(amount: u64, fee_bps: u16) -> Result<u64> {
+    let fee = (amount as u128)
+        .checked_mul(fee_bps as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .div_ceil(BPS_DENOMINATOR as u128);
+    u64::try_from(fee).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+/// Fees charged on a single trade
+#[derive(Clone, Copy)]
+struct TradeFees {
+    /// Claimable by the market's fee recipient
+    recipient: u64,
+    /// Paid out to LPs with their share of the pool
+    lp: u64,
+    /// Claimable by the config's protocol fee recipient
+    protocol: u64,
+}
+
+impl TradeFees {
+    /// Fees on a trade of `amount` USDC at the market's rates
+    /// Parimutuel trades are free; the house fee comes out of the pot at settlement
+    fn on(amount: u64, market: &MarketState) -> Result<Self> {
+        if market.mode == MarketMode::Parimutuel {
+            return Ok(Self {
+                recipient: 0,
+                lp: 0,
+                protocol: 0,
+            });
+        }
+        Ok(Self {
+            recipient: trading_fee(amount, market.fee_bps)?,
+            lp: trading_fee(amount, market.lp_fee_bps)?,
+            protocol: trading_fee(amount, market.protocol_fee_bps)?,
+        })
+    }
+
+    fn total(&self) -> Result<u64> {
+        self.recipient
+            .checked_add(self.lp)
+            .and_then(|fees| fees.checked_add(self.protocol))
+            .ok_or(ErrorCode::MathOverflow.into())
+    }
+
+    /// Books the fees against the market; the USDC itself sits in the vault
+    fn accrue(&self, market: &mut MarketState) -> Result<()> {
+        market.accrued_fees = market
+            .accrued_fees
+            .checked_add(self.recipient)
+            .ok_or(ErrorCode::MathOverflow)?;
+        market.lp_fees = market
+            .lp_fees
+            .checked_add(self.lp)
+            .ok_or(ErrorCode::MathOverflow)?;
+        market.protocol_fees = market
+            .protocol_fees
+            .checked_add(self.protocol)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+}
+
+/// Smallest amount that still leaves `net` once the market's trading fees are taken out
+fn gross_for_net(net: u64, market: &MarketState) -> Result<u64> {
+    let total_bps =
+        market.fee_bps as u64 + market.lp_fee_bps as u64 + market.protocol_fee_bps as u64;
+    let gross = (net as u128)
+        .checked_mul(BPS_DENOMINATOR as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        / (BPS_DENOMINATOR - total_bps) as u128;
+    let mut gross = u64::try_from(gross).map_err(|_| ErrorCode::MathOverflow)?;
+    while gross.saturating_sub(TradeFees::on(gross, market)?.total()?) < net {
+        gross = gross.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+    }
+    Ok(gross)
+}
+
+/// USDC the vault must hold for the outcome tokens still outstanding
+/// Before settlement any outcome can win, so the largest supply; after it each
+/// supply at its payout rate recorded at settlement. Redemptions round down, so
+/// rounding the total down still covers every one of them
+fn redeemable_liability(market: &MarketState, supplies: &[u64]) -> Result<u64> {
+    if !market.is_settled {
+        return Ok(supplies.iter().copied().max().unwrap_or(0));
+    }
+    let mut owed = 0u128;
+    for (supply, rate) in supplies.iter().zip(market.payout_rates) {
+        owed = owed
+            .checked_add(
+                (*supply as u128)
+                    .checked_mul(rate)
+                    .ok_or(ErrorCode::MathOverflow)?,
+            )
+            .ok_or(ErrorCode::MathOverflow)?;
+    }
+    u64::try_from(owed / PAYOUT_SCALE as u128).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+/// Pays `amount` out of a market-owned token account, signed by the market PDA
+fn transfer_from_market<'info>(
+    market: &Account<'info, MarketState>,
+    from: &Account<'info, TokenAccount>,
+    to: &Account<'info, TokenAccount>,
+    token_program: &Program<'info, Token>,
+    amount: u64,
+) -> Result<()> {
+    let seeds = &[
+        b"market_v2".as_ref(),
+        market.authority.as_ref(),
+        market.pda_name_seed(),
+        &[market.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let transfer_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        Transfer {
+            from: from.to_account_info(),
+            to: to.to_account_info(),
+            authority: market.to_account_info(),
+        },
+        signer,
+    );
+    token::transfer(transfer_ctx, amount)
+}
+
+/// Settles `market` on the result in `record`: fixes the payout rates against
+/// `collateral`, stops trading and keeps `record` for audits and disputes
+fn settle(market: &mut MarketState, collateral: u64, record: SettlementRecord) -> Result<()> {
+    let winning_outcome = market.outcome_for(record.fundraising_result)?;
+    let weights = market.settlement_weights(record.fundraising_result)?;
+    set_payout_rates(market, weights, collateral)?;
+
+    market.is_settled = true;
+    market.winning_outcome = winning_outcome;
+
+    msg!(
+        "Market settled: {} | Goal: {} | Result: {} | Winner: {:?} | Payout rates: {:?} | By: {}",
+        market.project_name,
+        market.fundraising_goal,
+        record.fundraising_result,
+        winning_outcome,
+        &market.payout_rates[..market.outcome_count as usize],
+        record.settler
+    );
+
+    market.settlement = record;
+    Ok(())
+}
+
+/// Fixes what each outcome token redeems for, given the share of a full payout
+/// (`PAYOUT_SCALE`) each is worth and the collateral in the vault
+/// LMSR markets pay `weights` in full unless the vault can't cover that, in which
+/// case every rate is cut by the same factor
+/// Parimutuel markets take the house and protocol fees and split the pot by
+/// `weights`, each outcome's part shared by its stakes; parts with no stakes go
+/// to the house, unless no stake was on a paying outcome at all, in which case
+/// every stake is refunded as if the market were voided
+fn set_payout_rates(
+    market: &mut MarketState,
+    weights: [u128; MAX_OUTCOMES],
+    collateral: u64,
+) -> Result<()> {
+    let supplies = market.supplies();
+    let mut rates = [0u128; MAX_OUTCOMES];
+    match market.mode {
+        MarketMode::Lmsr => {
+            let mut owed = 0u128;
+            for (supply, weight) in supplies.iter().zip(weights) {
+                let due = (*supply as u128)
+                    .checked_mul(weight)
+                    .ok_or(ErrorCode::MathOverflow)?;
+                owed = owed.checked_add(due).ok_or(ErrorCode::MathOverflow)?;
+            }
+            let available = collateral as u128 * PAYOUT_SCALE as u128;
+            for (rate, weight) in rates.iter_mut().zip(weights) {
+                *rate = if available >= owed {
+                    weight
+                } else {
+                    weight * available / owed
+                };
+            }
+        }
+        MarketMode::Parimutuel => {
+            let backed = supplies
+                .iter()
+                .zip(weights)
+                .any(|(supply, weight)| *supply > 0 && weight > 0);
+            if !backed {
+                return set_refund_rates(market, collateral);
+            }
+            let mut house_fee = trading_fee(market.usdc_liquidity, market.fee_bps)?;
+            let protocol_fee = trading_fee(market.usdc_liquidity, market.protocol_fee_bps)?;
+            let pot = (market.usdc_liquidity - house_fee - protocol_fee) as u128;
+            for (index, supply) in supplies.iter().enumerate() {
+                let share = pot * weights[index];
+                if *supply > 0 {
+                    rates[index] = share / *supply as u128;
+                } else {
+                    house_fee += (share / PAYOUT_SCALE as u128) as u64;
+                }
+            }
+            market.usdc_liquidity = market
+                .usdc_liquidity
+                .checked_sub(house_fee + protocol_fee)
+                .ok_or(ErrorCode::MathOverflow)?;
+            market.accrued_fees = market
+                .accrued_fees
+                .checked_add(house_fee)
+                .ok_or(ErrorCode::MathOverflow)?;
+            market.protocol_fees = market
+                .protocol_fees
+                .checked_add(protocol_fee)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+    }
+    market.payout_rates = rates;
+    Ok(())
+}
+
+/// Fixes what each outcome token refunds for when a market is voided
+/// LMSR tokens are worth their current price, which sums to one across outcomes;
+/// parimutuel stakes all get the same share of the pot, with no house fee
+fn set_refund_rates(market: &mut MarketState, collateral: u64) -> Result<()> {
+    match market.mode {
+        MarketMode::Lmsr => {
+            let quantities = market.quantities();
+            let mut weights = [0u128; MAX_OUTCOMES];
+            for (index, weight) in weights[..quantities.len()].iter_mut().enumerate() {
+                *weight = lmsr::price(&quantities, market.lmsr_b, index)? * PAYOUT_SCALE as u128
+                    / lmsr::WAD;
+            }
+            set_payout_rates(market, weights, collateral)
+        }
+        MarketMode::Parimutuel => {
+            let total_stake: u128 = market.supplies().iter().map(|s| *s as u128).sum();
+            let rate = (market.usdc_liquidity as u128 * PAYOUT_SCALE as u128)
+                .checked_div(total_stake)
+                .unwrap_or(0)
+                .min(PAYOUT_SCALE as u128);
+            let mut rates = [0u128; MAX_OUTCOMES];
+            rates[..market.outcome_count as usize].fill(rate);
+            market.payout_rates = rates;
+            Ok(())
+        }
+    }
+}
+
+/// Vault balance backing outcome tokens, i.e. excluding fees owed elsewhere
+fn vault_collateral(market: &MarketState, vault: &TokenAccount) -> Result<u64> {
+    let fees = market
+        .accrued_fees
+        .checked_add(market.lp_fees)
+        .and_then(|fees| fees.checked_add(market.protocol_fees))
+        .ok_or(ErrorCode::MathOverflow)?;
+    Ok(vault.amount.saturating_sub(fees))
+}
+
+/// Enforces `vault collateral >= redeemable liability` after a state change
+/// Reloads the vault so transfers made by CPIs in the same instruction are seen;
+/// supplies come from the market's own tally of what it minted and burned
+fn assert_solvent<'info>(
+    market: &MarketState,
+    vault: &mut Account<'info, TokenAccount>,
+) -> Result<()> {
+    vault.reload()?;
+
+    let liability = redeemable_liability(market, &market.supplies())?;
+    require!(
+        vault_collateral(market, vault)? >= liability,
+        ErrorCode::VaultInsolvent
+    );
+    Ok(())
+}
+
+/// Expected value of the pool at current prices: its collateral minus the
+/// price-weighted claim of every outstanding outcome token
+/// Always positive while the solvency invariant holds
+fn pool_value(market: &MarketState, supplies: &[u64]) -> Result<u64> {
+    let quantities = market.quantities();
+    let mut claims = 0u128;
+    for (index, supply) in supplies.iter().enumerate() {
+        let price = lmsr::price(&quantities, market.lmsr_b, index)?;
+        claims = claims
+            .checked_add((*supply as u128).checked_mul(price).ok_or(ErrorCode::MathOverflow)?)
+            .ok_or(ErrorCode::MathOverflow)?;
+    }
+    let claims = u64::try_from(claims.div_ceil(lmsr::WAD)).map_err(|_| ErrorCode::MathOverflow)?;
+    Ok(market.usdc_liquidity.saturating_sub(claims))
+}
+
+/// Collateral the pool could release and still pay every outcome's supply
+/// in the worst case, given LMSR state `quantities` / `b`
+/// For each outcome the pool may still owe `supply_i - q_i + C(q)`
+fn releasable_collateral(
+    market: &MarketState,
+    quantities: &[u64],
+    b: u64,
+    supplies: &[u64],
+) -> Result<u64> {
+    let cost = lmsr::total_cost(quantities, b)?;
+    let mut releasable = market.usdc_liquidity;
+    for (quantity, supply) in quantities.iter().zip(supplies.iter().copied()) {
+        let covered = market.usdc_liquidity as u128 + *quantity as u128;
+        let owed = supply as u128 + cost as u128;
+        releasable = releasable.min(covered.saturating_sub(owed) as u64);
+    }
+    Ok(releasable)
+}
+
+/// Moves `quantities` by a trade of `delta` tokens of outcome `index`
+/// LMSR quantities are re-based; parimutuel quantities are plain stake totals
+fn record_trade(
+    market: &MarketState,
+    quantities: &mut [u64],
+    index: usize,
+    delta: i128,
+) -> Result<()> {
+    match market.mode {
+        MarketMode::Lmsr => lmsr::apply_trade(quantities, index, delta),
+        MarketMode::Parimutuel => {
+            let stake = u64::try_from(delta).map_err(|_| ErrorCode::SellingDisabled)?;
+            quantities[index] = quantities[index]
+                .checked_add(stake)
+                .ok_or(ErrorCode::MathOverflow)?;
+            Ok(())
+        }
+    }
+}
+
+/// Validates a complete set of `(mint, user token account)` pairs, one per outcome
+/// in outcome order, as passed to `split` and `merge`
+fn complete_set_accounts<'info>(
+    market: &MarketState,
+    accounts: &'info [AccountInfo<'info>],
+    user: &Pubkey,
+) -> Result<Vec<(AccountInfo<'info>, AccountInfo<'info>)>> {
+    require!(
+        accounts.len() == 2 * market.outcome_count as usize,
+        ErrorCode::InvalidOutcomeMint
+    );
+    accounts
+        .chunks(2)
+        .enumerate()
+        .map(|(index, pair)| {
+            let (mint, token_account) = (&pair[0], &pair[1]);
+            require_keys_eq!(
+                mint.key(),
+                market.outcome_mints[index],
+                ErrorCode::InvalidOutcomeMint
+            );
+            let holding = Account::<TokenAccount>::try_from(token_account)?;
+            require_keys_eq!(holding.mint, mint.key(), ErrorCode::OutcomeMintMismatch);
+            require_keys_eq!(holding.owner, *user, ErrorCode::TokenAccountOwnerMismatch);
+            Ok((mint.clone(), token_account.clone()))
+        })
+        .collect()
+}
+
+/// Rejects a trade submitted with an `expires_at` that is already in the past
+fn check_expiry(expires_at: Option<i64>, clock: &Clock) -> Result<()> {
+    if let Some(expires_at) = expires_at {
+        require!(
+            clock.unix_timestamp <= expires_at,
+            ErrorCode::TransactionExpired
+        );
+    }
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(params: InitializeParams)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + MarketState::LEN,
+        seeds = [b"market_v2", authority.key().as_ref(), &market_seed(&params.project_name)],
+        bump
+    )]
+    pub market: Account<'info, MarketState>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Box<Account<'info, ProgramConfig>>,
+
+    /// Registry page the new market's id falls in; created by its first market
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + MarketRegistryPage::LEN,
+        seeds = [
+            b"registry".as_ref(),
+            (config.market_count / REGISTRY_PAGE_SIZE as u64).to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub registry_page: Box<Account<'info, MarketRegistryPage>>,
+
+    #[account(mut)]
+    pub authority_usdc_account: Box<Account<'info, TokenAccount>>,
+
+    pub usdc_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"lp_mint", market.key().as_ref()],
+        bump,
+        mint::decimals = usdc_mint.decimals,
+        mint::authority = market,
+    )]
+    pub lp_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = authority,
+        associated_token::mint = lp_mint,
+        associated_token::authority = authority,
+    )]
+    pub authority_lp_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        bump,
+        token::mint = usdc_mint,
+        token::authority = market,
+    )]
+    pub usdc_liquidity_account: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: Campaign the market is bound to, if `campaign_program` is given; its
+    /// owner and layout are checked in the instruction
+    pub campaign: Option<UncheckedAccount<'info>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+impl<'info> Initialize<'info> {
+    /// Creates the mint for outcome `index` at its PDA `[b"mint", market, seed, bump]`,
+    /// with the market as mint authority and the collateral's decimals
+    /// Being created here, it can't start with any supply
+    /// The account is funded, allocated and assigned rather than created, so
+    /// lamports sent to the address beforehand can't block the market
+    fn create_outcome_mint(
+        &self,
+        program_id: &Pubkey,
+        mint: &AccountInfo<'info>,
+        kind: MarketKind,
+        index: u8,
+        bump: u8,
+    ) -> Result<()> {
+        let market_key = self.market.key();
+        let outcome_seed = outcome_mint_seed(kind, index);
+        let seeds = &[b"mint".as_ref(), market_key.as_ref(), &outcome_seed, &[bump]];
+        let address = Pubkey::create_program_address(seeds, program_id)
+            .map_err(|_| error!(ErrorCode::InvalidOutcomeMint))?;
+        require_keys_eq!(mint.key(), address, ErrorCode::InvalidOutcomeMint);
+        let signer = &[&seeds[..]];
+
+        let rent = self.rent.minimum_balance(Mint::LEN);
+        let top_up = rent.saturating_sub(mint.lamports());
+        if top_up > 0 {
+            let transfer_ctx = CpiContext::new(
+                self.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: self.authority.to_account_info(),
+                    to: mint.clone(),
+                },
+            );
+            system_program::transfer(transfer_ctx, top_up)?;
+        }
+
+        let allocate_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            system_program::Allocate {
+                account_to_allocate: mint.clone(),
+            },
+            signer,
+        );
+        system_program::allocate(allocate_ctx, Mint::LEN as u64)?;
+
+        let assign_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            system_program::Assign {
+                account_to_assign: mint.clone(),
+            },
+            signer,
+        );
+        system_program::assign(assign_ctx, &token::ID)?;
+
+        let init_ctx = CpiContext::new(
+            self.token_program.to_account_info(),
+            token::InitializeMint2 { mint: mint.clone() },
+        );
+        token::initialize_mint2(init_ctx, self.usdc_mint.decimals, &market_key, None)
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + ProgramConfig::LEN,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::IndieStarMarket>,
+
+    /// Only the program's upgrade authority can create the config
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key())
+            @ ErrorCode::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct BuyTokens<'info> {
+    #[account(mut)]
+    pub market: Account<'info, MarketState>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// Mint of the traded outcome; checked against `outcome` in the instruction
+    #[account(mut)]
+    pub outcome_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.mint == outcome_mint.key() @ ErrorCode::OutcomeMintMismatch,
+        constraint = user_token_account.owner == user.key() @ ErrorCode::TokenAccountOwnerMismatch
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = user_usdc_account.mint == market.usdc_mint @ ErrorCode::CollateralMintMismatch,
+        constraint = user_usdc_account.owner == user.key() @ ErrorCode::TokenAccountOwnerMismatch
+    )]
+    pub user_usdc_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        bump
+    )]
+    pub usdc_liquidity_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> BuyTokens<'info> {
+    /// Moves `usdc_in` plus `fees` into the vault, mints `tokens_out` to the user and
+    /// records the trade against the LMSR quantities and the fee accruals
+    fn execute(
+        &mut self,
+        outcome: Outcome,
+        usdc_in: u64,
+        fees: TradeFees,
+        tokens_out: u64,
+    ) -> Result<()> {
+        let market = &self.market;
+        let bump = market.bump;
+        let authority = market.authority;
+        let name_seed = market.pda_name_seed().to_vec();
+        let mint = &self.outcome_mint;
+        require_keys_eq!(
+            mint.key(),
+            market.outcome_mints[outcome.index()],
+            ErrorCode::InvalidOutcomeMint
+        );
+
+        // Transfer USDC from user to liquidity pool; fees share the vault
+        let transfer_ctx = CpiContext::new(
+            self.token_program.to_account_info(),
+            Transfer {
+                from: self.user_usdc_account.to_account_info(),
+                to: self.usdc_liquidity_account.to_account_info(),
+                authority: self.user.to_account_info(),
+            },
+        );
+        token::transfer(
+            transfer_ctx,
+            usdc_in.checked_add(fees.total()?).ok_or(ErrorCode::MathOverflow)?,
+        )?;
+
+        // Mint tokens to user
+        let seeds = &[
+            b"market_v2".as_ref(),
+            authority.as_ref(),
+            name_seed.as_ref(),
+            &[bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let mint_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            token::MintTo {
+                mint: mint.to_account_info(),
+                to: self.user_token_account.to_account_info(),
+                authority: self.market.to_account_info(),
+            },
+            signer,
+        );
+        token::mint_to(mint_ctx, tokens_out)?;
+
+        // Update liquidity
+        let market = &mut self.market;
+        let mut quantities = market.quantities();
+        record_trade(market, &mut quantities, outcome.index(), tokens_out as i128)?;
+        market.set_quantities(&quantities);
+        market.add_supply(outcome.index(), tokens_out as i128)?;
+        market.usdc_liquidity = market
+            .usdc_liquidity
+            .checked_add(usdc_in)
+            .ok_or(ErrorCode::MathOverflow)?;
+        fees.accrue(market)?;
+
+        assert_solvent(&self.market, &mut self.usdc_liquidity_account)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct SellTokens<'info> {
+    #[account(mut)]
+    pub market: Account<'info, MarketState>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// Mint of the traded outcome; checked against `outcome` in the instruction
+    #[account(mut)]
+    pub outcome_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.mint == outcome_mint.key() @ ErrorCode::OutcomeMintMismatch,
+        constraint = user_token_account.owner == user.key() @ ErrorCode::TokenAccountOwnerMismatch
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = user_usdc_account.mint == market.usdc_mint @ ErrorCode::CollateralMintMismatch,
+        constraint = user_usdc_account.owner == user.key() @ ErrorCode::TokenAccountOwnerMismatch
+    )]
+    pub user_usdc_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        bump
+    )]
+    pub usdc_liquidity_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> SellTokens<'info> {
+    /// Burns `tokens_in` from the user, takes `proceeds` out of the pool, pays
+    /// `proceeds` less `fees` to the user and keeps the fees in the vault
+    fn execute(
+        &mut self,
+        outcome: Outcome,
+        tokens_in: u64,
+        proceeds: u64,
+        fees: TradeFees,
+    ) -> Result<()> {
+        let market = &self.market;
+        let bump = market.bump;
+        let authority = market.authority;
+        let name_seed = market.pda_name_seed().to_vec();
+        let mint = &self.outcome_mint;
+        require_keys_eq!(
+            mint.key(),
+            market.outcome_mints[outcome.index()],
+            ErrorCode::InvalidOutcomeMint
+        );
+
+        // Burn tokens from user (user is the authority of their own token account)
+        let burn_ctx = CpiContext::new(
+            self.token_program.to_account_info(),
+            token::Burn {
+                mint: mint.to_account_info(),
+                from: self.user_token_account.to_account_info(),
+                authority: self.user.to_account_info(),
+            },
+        );
+        token::burn(burn_ctx, tokens_in)?;
+
+        // Prepare signer seeds for USDC transfer from liquidity pool
+        let seeds = &[
+            b"market_v2".as_ref(),
+            authority.as_ref(),
+            name_seed.as_ref(),
+            &[bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        // Transfer USDC from liquidity pool to user
+        let transfer_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            Transfer {
+                from: self.usdc_liquidity_account.to_account_info(),
+                to: self.user_usdc_account.to_account_info(),
+                authority: self.market.to_account_info(),
+            },
+            signer,
+        );
+        token::transfer(
+            transfer_ctx,
+            proceeds.checked_sub(fees.total()?).ok_or(ErrorCode::MathOverflow)?,
+        )?;
+
+        // Update liquidity
+        let market = &mut self.market;
+        let mut quantities = market.quantities();
+        lmsr::apply_trade(&mut quantities, outcome.index(), -(tokens_in as i128))?;
+        market.set_quantities(&quantities);
+        market.add_supply(outcome.index(), -(tokens_in as i128))?;
+        market.usdc_liquidity = market
+            .usdc_liquidity
+            .checked_sub(proceeds)
+            .ok_or(ErrorCode::MathOverflow)?;
+        fees.accrue(market)?;
+
+        assert_solvent(&self.market, &mut self.usdc_liquidity_account)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct Split<'info> {
+    #[account(mut)]
+    pub market: Account<'info, MarketState>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = user_usdc_account.mint == market.usdc_mint @ ErrorCode::CollateralMintMismatch,
+        constraint = user_usdc_account.owner == user.key() @ ErrorCode::TokenAccountOwnerMismatch
+    )]
+    pub user_usdc_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        bump
+    )]
+    pub usdc_liquidity_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct Merge<'info> {
+    #[account(mut)]
+    pub market: Account<'info, MarketState>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = user_usdc_account.mint == market.usdc_mint @ ErrorCode::CollateralMintMismatch,
+        constraint = user_usdc_account.owner == user.key() @ ErrorCode::TokenAccountOwnerMismatch
+    )]
+    pub user_usdc_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        bump
+    )]
+    pub usdc_liquidity_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct GetQuote<'info> {
+    pub market: Account<'info, MarketState>,
+}
+
+#[derive(Accounts)]
+pub struct AddLiquidity<'info> {
+    #[account(
+        mut,
+        has_one = lp_mint @ ErrorCode::LpMintMismatch
+    )]
+    pub market: Account<'info, MarketState>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub lp_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = user_lp_account.mint == lp_mint.key() @ ErrorCode::LpMintMismatch,
+        constraint = user_lp_account.owner == user.key() @ ErrorCode::TokenAccountOwnerMismatch
+    )]
+    pub user_lp_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = user_usdc_account.mint == market.usdc_mint @ ErrorCode::CollateralMintMismatch,
+        constraint = user_usdc_account.owner == user.key() @ ErrorCode::TokenAccountOwnerMismatch
+    )]
+    pub user_usdc_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        bump
+    )]
+    pub usdc_liquidity_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveLiquidity<'info> {
+    #[account(
+        mut,
+        has_one = lp_mint @ ErrorCode::LpMintMismatch
+    )]
+    pub market: Account<'info, MarketState>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub lp_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = user_lp_account.mint == lp_mint.key() @ ErrorCode::LpMintMismatch,
+        constraint = user_lp_account.owner == user.key() @ ErrorCode::TokenAccountOwnerMismatch
+    )]
+    pub user_lp_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = user_usdc_account.mint == market.usdc_mint @ ErrorCode::CollateralMintMismatch,
+        constraint = user_usdc_account.owner == user.key() @ ErrorCode::TokenAccountOwnerMismatch
+    )]
+    pub user_usdc_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        bump
+    )]
+    pub usdc_liquidity_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimFees<'info> {
+    #[account(
+        mut,
+        has_one = fee_recipient @ ErrorCode::Unauthorized
+    )]
+    pub market: Account<'info, MarketState>,
+
+    pub fee_recipient: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = recipient_usdc_account.mint == market.usdc_mint @ ErrorCode::CollateralMintMismatch
+    )]
+    pub recipient_usdc_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        bump
+    )]
+    pub usdc_liquidity_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimProtocolFees<'info> {
+    #[account(mut)]
+    pub market: Account<'info, MarketState>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = protocol_fee_recipient @ ErrorCode::Unauthorized
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    pub protocol_fee_recipient: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = recipient_usdc_account.mint == market.usdc_mint @ ErrorCode::CollateralMintMismatch
+    )]
+    pub recipient_usdc_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        bump
+    )]
+    pub usdc_liquidity_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SettleMarket<'info> {
+    #[account(
+        mut,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub market: Account<'info, MarketState>,
+
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        bump
+    )]
+    pub usdc_liquidity_account: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct SetMarketPaused<'info> {
+    #[account(
+        mut,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub market: Account<'info, MarketState>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct VoidMarket<'info> {
+    #[account(mut)]
+    pub market: Account<'info, MarketState>,
+
+    /// Market authority, or anyone once the resolution deadline has passed
+    pub caller: Signer<'info>,
+
+    #[account(
+        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        bump
+    )]
+    pub usdc_liquidity_account: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct SettleFromFeed<'info> {
+    #[account(mut)]
+    pub market: Account<'info, MarketState>,
+
+    /// CHECK: address, owner and layout are checked against `market.fundraising_feed`
+    pub feed: UncheckedAccount<'info>,
+
+    pub caller: Signer<'info>,
+
+    #[account(
+        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        bump
+    )]
+    pub usdc_liquidity_account: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct SettleFromCampaign<'info> {
+    #[account(mut)]
+    pub market: Account<'info, MarketState>,
+
+    /// CHECK: address, owner and layout are checked against `market.campaign`
+    pub campaign: UncheckedAccount<'info>,
+
+    pub caller: Signer<'info>,
+
+    #[account(
+        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        bump
+    )]
+    pub usdc_liquidity_account: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct EarlySettle<'info> {
+    #[account(mut)]
+    pub market: Account<'info, MarketState>,
+
+    /// CHECK: must be the market's campaign or fundraising feed; checked in the instruction
+    pub source: UncheckedAccount<'info>,
+
+    pub caller: Signer<'info>,
+
+    #[account(
+        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        bump
+    )]
+    pub usdc_liquidity_account: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct CreateResolverCommittee<'info> {
+    #[account(
+        mut,
+        has_one = authority @ ErrorCode::Unauthorized
+    )]
+    pub market: Account<'info, MarketState>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + ResolverCommittee::LEN,
+        seeds = [b"resolvers", market.key().as_ref()],
+        bump
+    )]
+    pub committee: Account<'info, ResolverCommittee>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitResolution<'info> {
+    #[account(mut)]
+    pub market: Account<'info, MarketState>,
+
+    #[account(
+        mut,
+        seeds = [b"resolvers", market.key().as_ref()],
+        bump = committee.bump
+    )]
+    pub committee: Account<'info, ResolverCommittee>,
+
+    pub resolver: Signer<'info>,
+
+    #[account(
+        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        bump
+    )]
+    pub usdc_liquidity_account: Account<'info, TokenAccount>,
 }
 
 #[derive(Accounts)]
-#[instruction(fundraising_goal: u64, deadline: i64, project_name: String)]
-pub struct Initialize<'info> {
+pub struct ProposeResult<'info> {
+    pub market: Account<'info, MarketState>,
+
     #[account(
         init,
-        payer = authority,
-        space = 8 + MarketState::LEN,
-        seeds = [b"market_v2", authority.key().as_ref(), project_name.as_bytes()],
+        payer = proposer,
+        space = 8 + ResultProposal::LEN,
+        seeds = [b"proposal", market.key().as_ref()],
         bump
     )]
-    pub market: Account<'info, MarketState>,
+    pub proposal: Account<'info, ResultProposal>,
 
     #[account(mut)]
-    pub authority: Signer<'info>,
-
-    pub yes_mint: Account<'info, Mint>,
-    pub no_mint: Account<'info, Mint>,
-    pub usdc_mint: Account<'info, Mint>,
+    pub proposer: Signer<'info>,
 
     #[account(
-        init,
-        payer = authority,
-        seeds = [b"liquidity", market.key().as_ref(), b"yes"],
-        bump,
-        token::mint = yes_mint,
-        token::authority = market,
+        mut,
+        constraint = proposer_usdc_account.mint == market.usdc_mint @ ErrorCode::CollateralMintMismatch,
+        constraint = proposer_usdc_account.owner == proposer.key() @ ErrorCode::TokenAccountOwnerMismatch
     )]
-    pub yes_liquidity_account: Account<'info, TokenAccount>,
+    pub proposer_usdc_account: Account<'info, TokenAccount>,
 
     #[account(
-        init,
-        payer = authority,
-        seeds = [b"liquidity", market.key().as_ref(), b"no"],
-        bump,
-        token::mint = no_mint,
-        token::authority = market,
+        constraint = usdc_mint.key() == market.usdc_mint @ ErrorCode::CollateralMintMismatch
     )]
-    pub no_liquidity_account: Account<'info, TokenAccount>,
+    pub usdc_mint: Account<'info, Mint>,
 
+    /// Holds proposal and dispute bonds, apart from the market's collateral
     #[account(
         init,
-        payer = authority,
-        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        payer = proposer,
+        seeds = [b"bond", market.key().as_ref()],
         bump,
         token::mint = usdc_mint,
         token::authority = market,
     )]
-    pub usdc_liquidity_account: Account<'info, TokenAccount>,
+    pub bond_vault: Account<'info, TokenAccount>,
 
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-pub struct BuyTokens<'info> {
-    #[account(mut)]
+pub struct DisputeResult<'info> {
     pub market: Account<'info, MarketState>,
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"proposal", market.key().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, ResultProposal>,
 
-    /// CHECK: Validated in instruction
-    #[account(mut)]
-    pub yes_mint: AccountInfo<'info>,
+    pub disputer: Signer<'info>,
 
-    /// CHECK: Validated in instruction
-    #[account(mut)]
-    pub no_mint: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = disputer_usdc_account.mint == market.usdc_mint @ ErrorCode::CollateralMintMismatch,
+        constraint = disputer_usdc_account.owner == disputer.key() @ ErrorCode::TokenAccountOwnerMismatch
+    )]
+    pub disputer_usdc_account: Account<'info, TokenAccount>,
 
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"bond", market.key().as_ref()],
+        bump
+    )]
+    pub bond_vault: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
 
+#[derive(Accounts)]
+pub struct FinalizeResult<'info> {
     #[account(mut)]
-    pub user_usdc_account: Account<'info, TokenAccount>,
+    pub market: Account<'info, MarketState>,
 
     #[account(
         mut,
-        seeds = [b"liquidity", market.key().as_ref(), b"yes"],
-        bump
+        seeds = [b"proposal", market.key().as_ref()],
+        bump = proposal.bump
     )]
-    pub yes_liquidity_account: Account<'info, TokenAccount>,
+    pub proposal: Account<'info, ResultProposal>,
 
     #[account(
         mut,
-        seeds = [b"liquidity", market.key().as_ref(), b"no"],
-        bump
+        constraint = proposer_usdc_account.mint == market.usdc_mint @ ErrorCode::CollateralMintMismatch,
+        constraint = proposer_usdc_account.owner == proposal.proposer @ ErrorCode::TokenAccountOwnerMismatch
     )]
-    pub no_liquidity_account: Account<'info, TokenAccount>,
+    pub proposer_usdc_account: Account<'info, TokenAccount>,
 
     #[account(
         mut,
+        seeds = [b"bond", market.key().as_ref()],
+        bump
+    )]
+    pub bond_vault: Account<'info, TokenAccount>,
+
+    #[account(
         seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
         bump
     )]
@@ -498,60 +3286,132 @@ pub struct BuyTokens<'info> {
 }
 
 #[derive(Accounts)]
-pub struct SellTokens<'info> {
-    #[account(mut)]
+pub struct ResolveDispute<'info> {
+    #[account(
+        mut,
+        constraint = market.optimistic_oracle.map(|oracle| oracle.arbiter) == Some(arbiter.key())
+            @ ErrorCode::Unauthorized
+    )]
     pub market: Account<'info, MarketState>,
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"proposal", market.key().as_ref()],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, ResultProposal>,
 
-    /// CHECK: Validated in instruction
-    #[account(mut)]
-    pub yes_mint: AccountInfo<'info>,
+    pub arbiter: Signer<'info>,
 
-    /// CHECK: Validated in instruction
-    #[account(mut)]
-    pub no_mint: AccountInfo<'info>,
+    /// USDC account of the proposer or disputer, whichever the ruling favours
+    #[account(
+        mut,
+        constraint = winner_usdc_account.mint == market.usdc_mint @ ErrorCode::CollateralMintMismatch
+    )]
+    pub winner_usdc_account: Account<'info, TokenAccount>,
 
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"bond", market.key().as_ref()],
+        bump
+    )]
+    pub bond_vault: Account<'info, TokenAccount>,
 
-    #[account(mut)]
-    pub user_usdc_account: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        bump
+    )]
+    pub usdc_liquidity_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ReclaimBond<'info> {
+    pub market: Account<'info, MarketState>,
 
     #[account(
         mut,
-        seeds = [b"liquidity", market.key().as_ref(), b"yes"],
-        bump
+        seeds = [b"proposal", market.key().as_ref()],
+        bump = proposal.bump
     )]
-    pub yes_liquidity_account: Account<'info, TokenAccount>,
+    pub proposal: Account<'info, ResultProposal>,
+
+    pub claimant: Signer<'info>,
 
     #[account(
         mut,
-        seeds = [b"liquidity", market.key().as_ref(), b"no"],
-        bump
+        constraint = claimant_usdc_account.mint == market.usdc_mint @ ErrorCode::CollateralMintMismatch,
+        constraint = claimant_usdc_account.owner == claimant.key() @ ErrorCode::TokenAccountOwnerMismatch
     )]
-    pub no_liquidity_account: Account<'info, TokenAccount>,
+    pub claimant_usdc_account: Account<'info, TokenAccount>,
 
     #[account(
         mut,
-        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        seeds = [b"bond", market.key().as_ref()],
         bump
     )]
-    pub usdc_liquidity_account: Account<'info, TokenAccount>,
+    pub bond_vault: Account<'info, TokenAccount>,
 
     pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-pub struct SettleMarket<'info> {
+pub struct ResizeMarket<'info> {
+    /// CHECK: may be too short to deserialize as `MarketState`; the owner is checked
+    /// here and the discriminator in the instruction
+    #[account(mut, owner = crate::ID)]
+    pub market: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateLegacyMarket<'info> {
+    /// CHECK: in the legacy layout, which doesn't deserialize as `MarketState`; the
+    /// owner is checked here and the discriminator, size and address in the instruction
+    #[account(mut, owner = crate::ID)]
+    pub market: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
     #[account(
         mut,
-        has_one = authority @ ErrorCode::Unauthorized
+        seeds = [b"config"],
+        bump = config.bump
     )]
-    pub market: Account<'info, MarketState>,
+    pub config: Box<Account<'info, ProgramConfig>>,
 
-    pub authority: Signer<'info>,
+    /// Registry page the market's new id falls in
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + MarketRegistryPage::LEN,
+        seeds = [
+            b"registry".as_ref(),
+            (config.market_count / REGISTRY_PAGE_SIZE as u64).to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub registry_page: Box<Account<'info, MarketRegistryPage>>,
+
+    /// The market's YES mint; checked in the instruction
+    pub yes_mint: Account<'info, Mint>,
+
+    /// The market's NO mint; checked in the instruction
+    pub no_mint: Account<'info, Mint>,
+
+    #[account(
+        seeds = [b"liquidity", market.key().as_ref(), b"usdc"],
+        bump
+    )]
+    pub usdc_liquidity_account: Account<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
@@ -562,18 +3422,22 @@ pub struct RedeemTokens<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
 
-    /// CHECK: Validated in instruction
-    #[account(mut)]
-    pub yes_mint: AccountInfo<'info>,
-
-    /// CHECK: Validated in instruction
+    /// Mint of the winning outcome; checked in the instruction
     #[account(mut)]
-    pub no_mint: AccountInfo<'info>,
+    pub outcome_mint: Account<'info, Mint>,
 
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = user_token_account.mint == outcome_mint.key() @ ErrorCode::OutcomeMintMismatch,
+        constraint = user_token_account.owner == user.key() @ ErrorCode::TokenAccountOwnerMismatch
+    )]
     pub user_token_account: Account<'info, TokenAccount>,
 
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = user_usdc_account.mint == market.usdc_mint @ ErrorCode::CollateralMintMismatch,
+        constraint = user_usdc_account.owner == user.key() @ ErrorCode::TokenAccountOwnerMismatch
+    )]
     pub user_usdc_account: Account<'info, TokenAccount>,
 
     #[account(
@@ -586,54 +3450,596 @@ pub struct RedeemTokens<'info> {
     pub token_program: Program<'info, Token>,
 }
 
+/// Program-wide settings and the market counter, at PDA `[b"config"]`
+#[account]
+pub struct ProgramConfig {
+    pub admin: Pubkey,
+    /// Markets created so far; the next market's id
+    pub market_count: u64,
+    /// Trading fee new markets pay to `protocol_fee_recipient`, on top of their own
+    pub protocol_fee_bps: u16,
+    pub protocol_fee_recipient: Pubkey,
+    pub bump: u8,
+}
+
+impl ProgramConfig {
+    pub const LEN: usize = 32 + // admin
+        8 +  // market_count
+        2 +  // protocol_fee_bps
+        32 + // protocol_fee_recipient
+        1; // bump
+}
+
+/// One page of the market registry, at PDA `[b"registry", page.to_le_bytes()]`
+/// Page `n` holds the addresses of markets `n * REGISTRY_PAGE_SIZE` onwards, in id
+/// order, so clients can walk pages `0..=market_count / REGISTRY_PAGE_SIZE`
+#[account]
+pub struct MarketRegistryPage {
+    pub page: u64,
+    pub bump: u8,
+    pub markets: Vec<Pubkey>,
+}
+
+impl MarketRegistryPage {
+    pub const LEN: usize = 8 + // page
+        1 + // bump
+        4 + 32 * REGISTRY_PAGE_SIZE; // markets (4 byte prefix + max REGISTRY_PAGE_SIZE keys)
+}
+
+/// New fields go at the end, so accounts created since `resize_market` was added
+/// can be grown with it and read them as zero
+/// Markets in the original `market_v2` layout, whose fields sit at different
+/// offsets, are converted by `migrate_legacy_market` instead
 #[account]
 pub struct MarketState {
+    /// Sequential id assigned from `ProgramConfig::market_count`
+    pub market_id: u64,
     pub authority: Pubkey,
-    pub yes_mint: Pubkey,
-    pub no_mint: Pubkey,
+    pub kind: MarketKind,
+    pub outcome_count: u8,
+    /// Mint of each outcome's token; only the first `outcome_count` are set
+    pub outcome_mints: [Pubkey; MAX_OUTCOMES],
+    /// Categorical bucket boundaries in bps of the goal, ascending; only the first
+    /// `outcome_count - 1` are used
+    pub bucket_bounds_bps: [u32; MAX_OUTCOMES - 1],
+    /// Fundraising range a scalar market's LONG token tracks; unused otherwise
+    pub scalar_range: ScalarRange,
     pub usdc_mint: Pubkey,
     pub fundraising_goal: u64,
-    pub deadline: i64,
+    /// When the campaign ends; trading closes at `trading_close_ts`, no later, and
+    /// settlement opens at `settlement_available_ts`, no earlier
+    pub campaign_end_ts: i64,
+    /// Last moment the market can be settled; after it anyone can void it
+    pub resolution_deadline: i64,
     pub project_name: String,
-    pub yes_liquidity: u64,
-    pub no_liquidity: u64,
+    /// `market_seed(project_name)`, kept for the market PDA's signer seeds; see
+    /// `pda_name_seed`
+    pub name_seed: [u8; 32],
+    /// Per-outcome quantities, see `quantities`
+    pub outcome_quantities: [u64; MAX_OUTCOMES],
+    /// Outcome tokens minted and not yet burned through the program
+    /// An upper bound on the real supply, since holders can also burn directly
+    pub outcome_supplies: [u64; MAX_OUTCOMES],
     pub usdc_liquidity: u64,
+    pub lmsr_b: u64,
+    pub fee_bps: u16,
+    pub fee_recipient: Pubkey,
+    pub accrued_fees: u64,
+    pub lp_mint: Pubkey,
+    pub lp_fee_bps: u16,
+    pub lp_fees: u64,
     pub is_settled: bool,
+    /// Cancelled by `void_market`; `is_settled` is set too and every outcome
+    /// redeems at its refund rate
+    pub is_voided: bool,
     pub winning_outcome: Option<Outcome>,
     pub bump: u8,
+    /// USDC paid per token of each outcome, scaled by PAYOUT_SCALE; set at settlement
+    pub payout_rates: [u128; MAX_OUTCOMES],
+    pub mode: MarketMode,
+    /// What the market was settled or voided on; zeroed until then
+    pub settlement: SettlementRecord,
+    /// Bonded proposal settlement, if enabled at initialize
+    pub optimistic_oracle: Option<OptimisticOracleConfig>,
+    /// Settled by its `ResolverCommittee` instead of the authority
+    pub has_resolver_committee: bool,
+    /// Oracle account `settle_from_feed` reads the result from, if enabled at initialize
+    pub fundraising_feed: Option<FeedConfig>,
+    /// Campaign the market is bound to and settles on, if any
+    pub campaign: Option<CampaignBinding>,
+    /// Trades and liquidity deposits are accepted until this time
+    pub trading_close_ts: i64,
+    /// Settlement by any path except `early_settle` opens at this time
+    pub settlement_available_ts: i64,
+    /// Set by `pause_market`; trading, splits, liquidity deposits and settlement are rejected
+    pub paused: bool,
+    /// Protocol fee rate copied from the config at initialize
+    pub protocol_fee_bps: u16,
+    /// Protocol fees accrued and not yet claimed; held in the vault like `accrued_fees`
+    pub protocol_fees: u64,
+    /// Bytes of `name_seed` in the market PDA's seeds: 32, or the name's length for
+    /// markets migrated from the original `market_v2` layout
+    pub name_seed_len: u8,
 }
 
 impl MarketState {
-    pub const LEN: usize = 32 + // authority
-        32 + // yes_mint
-        32 + // no_mint
+    pub const LEN: usize = 8 + // market_id
+        32 + // authority
+        1 +  // kind
+        1 +  // outcome_count
+        32 * MAX_OUTCOMES + // outcome_mints
+        4 * (MAX_OUTCOMES - 1) + // bucket_bounds_bps
+        8 + 8 + // scalar_range
         32 + // usdc_mint
         8 +  // fundraising_goal
-        8 +  // deadline
-        4 + 256 + // project_name (4 byte prefix + max 256 chars)
-        8 +  // yes_liquidity
-        8 +  // no_liquidity
+        8 +  // campaign_end_ts
+        8 +  // resolution_deadline
+        4 + MAX_PROJECT_NAME_LEN + // project_name (4 byte prefix + max 256 chars)
+        32 + // name_seed
+        8 * MAX_OUTCOMES + // outcome_quantities
+        8 * MAX_OUTCOMES + // outcome_supplies
         8 +  // usdc_liquidity
+        8 +  // lmsr_b
+        2 +  // fee_bps
+        32 + // fee_recipient
+        8 +  // accrued_fees
+        32 + // lp_mint
+        2 +  // lp_fee_bps
+        8 +  // lp_fees
         1 +  // is_settled
+        1 +  // is_voided
         1 + 1 + // winning_outcome (Option<Outcome>)
+        1 + // bump
+        16 * MAX_OUTCOMES + // payout_rates
+        1 + // mode
+        SettlementRecord::LEN + // settlement
+        1 + OptimisticOracleConfig::LEN + // optimistic_oracle
+        1 + // has_resolver_committee
+        1 + FeedConfig::LEN + // fundraising_feed
+        1 + CampaignBinding::LEN + // campaign
+        8 + // trading_close_ts
+        8 + // settlement_available_ts
+        1 + // paused
+        2 + // protocol_fee_bps
+        8 + // protocol_fees
+        1; // name_seed_len
+
+    /// Size of the oldest layout `resize_market` can grow, the first released with it
+    /// Fields appended in later versions are subtracted here as they are added
+    pub const MIN_RESIZABLE_LEN: usize = Self::LEN;
+
+    /// Last seed of the market PDA
+    /// Markets migrated from the original `market_v2` layout were derived from the
+    /// raw project name, which `name_seed` holds for them instead of its hash
+    pub fn pda_name_seed(&self) -> &[u8] {
+        &self.name_seed[..self.name_seed_len as usize]
+    }
+
+    /// LMSR quantities, indexed by `Outcome::index`
+    /// Only the gaps between them matter, so they are stored re-based to a zero minimum
+    /// In parimutuel markets these are the total stakes on each outcome instead
+    pub fn quantities(&self) -> Vec<u64> {
+        self.outcome_quantities[..self.outcome_count as usize].to_vec()
+    }
+
+    pub fn set_quantities(&mut self, quantities: &[u64]) {
+        self.outcome_quantities[..quantities.len()].copy_from_slice(quantities);
+    }
+
+    /// Outstanding supply of each outcome, indexed by `Outcome::index`
+    pub fn supplies(&self) -> Vec<u64> {
+        self.outcome_supplies[..self.outcome_count as usize].to_vec()
+    }
+
+    /// Records `delta` tokens of outcome `index` minted (positive) or burned (negative)
+    pub fn add_supply(&mut self, index: usize, delta: i128) -> Result<()> {
+        let supply = self.outcome_supplies[index] as i128 + delta;
+        self.outcome_supplies[index] =
+            u64::try_from(supply).map_err(|_| ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Rejects an outcome this market doesn't have
+    pub fn check_outcome(&self, outcome: Outcome) -> Result<()> {
+        require!(
+            outcome.index() < self.outcome_count as usize,
+            ErrorCode::InvalidOutcome
+        );
+        Ok(())
+    }
+
+    /// Position of `mint` among this market's outcome mints
+    pub fn outcome_of_mint(&self, mint: &Pubkey) -> Option<usize> {
+        self.outcome_mints[..self.outcome_count as usize]
+            .iter()
+            .position(|outcome_mint| outcome_mint == mint)
+    }
+
+    /// Outcome that wins for a reported `fundraising_result`
+    /// Binary: YES if the goal was met, else NO
+    /// Categorical: the bucket the result falls in, with each bound belonging to the
+    /// bucket above it
+    /// Scalar markets have no single winner
+    pub fn outcome_for(&self, fundraising_result: u64) -> Result<Option<Outcome>> {
+        match self.kind {
+            MarketKind::Binary => Ok(Some(if fundraising_result >= self.fundraising_goal {
+                Outcome::YES
+            } else {
+                Outcome::NO
+            })),
+            MarketKind::Scalar => Ok(None),
+            MarketKind::Categorical => {
+                let bounds = &self.bucket_bounds_bps[..self.outcome_count as usize - 1];
+                let mut bucket = 0;
+                for bound_bps in bounds {
+                    let bound = (self.fundraising_goal as u128)
+                        .checked_mul(*bound_bps as u128)
+                        .ok_or(ErrorCode::MathOverflow)?
+                        / BPS_DENOMINATOR as u128;
+                    if (fundraising_result as u128) < bound {
+                        break;
+                    }
+                    bucket += 1;
+                }
+                Ok(Some(Outcome(bucket)))
+            }
+        }
+    }
+
+    /// Share of a full payout (`PAYOUT_SCALE`) each outcome's token is worth for a
+    /// reported `fundraising_result`
+    /// Scalar: LONG gets `clamp((result - lower) / (upper - lower), 0, 1)` and SHORT
+    /// the complement; otherwise the winning outcome gets it all
+    pub fn settlement_weights(&self, fundraising_result: u64) -> Result<[u128; MAX_OUTCOMES]> {
+        let mut weights = [0u128; MAX_OUTCOMES];
+        match self.outcome_for(fundraising_result)? {
+            Some(outcome) => weights[outcome.index()] = PAYOUT_SCALE as u128,
+            None => {
+                let ScalarRange {
+                    lower_bound,
+                    upper_bound,
+                } = self.scalar_range;
+                let reached = fundraising_result.clamp(lower_bound, upper_bound) - lower_bound;
+                let long = reached as u128 * PAYOUT_SCALE as u128
+                    / (upper_bound - lower_bound) as u128;
+                weights[Outcome::LONG.index()] = long;
+                weights[Outcome::SHORT.index()] = PAYOUT_SCALE as u128 - long;
+            }
+        }
+        Ok(weights)
+    }
+}
+
+/// Index of an outcome within its market
+/// Binary markets have `Outcome::YES` and `Outcome::NO`, scalar markets
+/// `Outcome::LONG` and `Outcome::SHORT`; categorical markets number their
+/// buckets from the lowest result up
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Outcome(pub u8);
+
+impl Outcome {
+    pub const YES: Outcome = Outcome(0);
+    pub const NO: Outcome = Outcome(1);
+    pub const LONG: Outcome = Outcome(0);
+    pub const SHORT: Outcome = Outcome(1);
+
+    /// Position of this outcome in the market's per-outcome arrays
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// What a market's outcomes stand for
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum MarketKind {
+    /// YES / NO on whether the fundraising goal is met
+    Binary,
+    /// One outcome per bucket of the fundraising result, bounded by `bucket_bounds_bps`
+    Categorical,
+    /// LONG / SHORT paying out by where the result lands in `scalar_range`
+    Scalar,
+}
+
+/// M-of-N committee that settles a market, at PDA `[b"resolvers", market]`
+#[account]
+pub struct ResolverCommittee {
+    pub market: Pubkey,
+    /// Agreeing votes needed to settle
+    pub threshold: u8,
+    pub resolvers: Vec<Pubkey>,
+    /// Result each resolver voted for, in `resolvers` order
+    pub votes: Vec<Option<u64>>,
+    pub bump: u8,
+}
+
+impl ResolverCommittee {
+    pub const LEN: usize = 32 + // market
+        1 + // threshold
+        4 + 32 * MAX_RESOLVERS + // resolvers (4 byte prefix + max MAX_RESOLVERS keys)
+        4 + 9 * MAX_RESOLVERS + // votes
+        1; // bump
+}
+
+/// Result proposed for an optimistic-oracle market, at PDA `[b"proposal", market]`
+/// Bonds sit in the market's bond vault `[b"bond", market]` until paid out
+#[account]
+pub struct ResultProposal {
+    pub market: Pubkey,
+    pub proposer: Pubkey,
+    pub fundraising_result: u64,
+    pub evidence_hash: [u8; 32],
+    pub evidence_uri: String,
+    pub proposed_at: i64,
+    /// Bonds still held for each side; zeroed once paid out
+    pub proposer_bond: u64,
+    pub disputer: Option<Pubkey>,
+    pub disputer_bond: u64,
+    pub bump: u8,
+}
+
+impl ResultProposal {
+    pub const LEN: usize = 32 + // market
+        32 + // proposer
+        8 +  // fundraising_result
+        32 + // evidence_hash
+        4 + MAX_EVIDENCE_URI_LEN + // evidence_uri
+        8 +  // proposed_at
+        8 +  // proposer_bond
+        1 + 32 + // disputer
+        8 +  // disputer_bond
         1; // bump
+
+    /// Moment the proposal can no longer be disputed
+    pub fn dispute_ends_at(&self, oracle: &OptimisticOracleConfig) -> Result<i64> {
+        self.proposed_at
+            .checked_add(oracle.dispute_window)
+            .ok_or(ErrorCode::MathOverflow.into())
+    }
+}
+
+/// Optimistic-oracle settings for a market
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct OptimisticOracleConfig {
+    /// Decides disputed proposals
+    pub arbiter: Pubkey,
+    /// USDC posted by proposers and disputers
+    pub bond: u64,
+    /// Seconds after a proposal during which it can be disputed
+    pub dispute_window: i64,
+}
+
+impl OptimisticOracleConfig {
+    pub const LEN: usize = 32 + // arbiter
+        8 + // bond
+        8; // dispute_window
+}
+
+/// Where and how to read the amount raised from an oracle account
+/// The account holds a publisher pubkey, a little-endian u64 amount and a
+/// little-endian i64 update time at the given byte offsets
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct FeedConfig {
+    pub feed: Pubkey,
+    /// Program that must own the feed account
+    pub owner_program: Pubkey,
+    /// Key the feed must name as its publisher
+    pub publisher: Pubkey,
+    pub publisher_offset: u16,
+    pub value_offset: u16,
+    pub timestamp_offset: u16,
+    /// Oldest reading accepted, in seconds
+    pub max_staleness: i64,
+}
+
+impl FeedConfig {
+    pub const LEN: usize = 32 + // feed
+        32 + // owner_program
+        32 + // publisher
+        2 + 2 + 2 + // offsets
+        8; // max_staleness
+
+    /// Reads the amount raised and its update time from `feed`, checking its address,
+    /// owner and publisher
+    pub fn read(&self, feed: &AccountInfo) -> Result<FeedReading> {
+        require_keys_eq!(feed.key(), self.feed, ErrorCode::FeedMismatch);
+        require_keys_eq!(*feed.owner, self.owner_program, ErrorCode::FeedMismatch);
+
+        let data = feed.try_borrow_data()?;
+        let field = |offset: u16, len: usize| -> Result<&[u8]> {
+            data.get(offset as usize..offset as usize + len)
+                .ok_or(ErrorCode::InvalidFeedData.into())
+        };
+        let publisher = Pubkey::try_from(field(self.publisher_offset, 32)?)
+            .map_err(|_| ErrorCode::InvalidFeedData)?;
+        require_keys_eq!(publisher, self.publisher, ErrorCode::FeedPublisherMismatch);
+
+        let value = u64::from_le_bytes(
+            field(self.value_offset, 8)?
+                .try_into()
+                .map_err(|_| ErrorCode::InvalidFeedData)?,
+        );
+        let updated_at = i64::from_le_bytes(
+            field(self.timestamp_offset, 8)?
+                .try_into()
+                .map_err(|_| ErrorCode::InvalidFeedData)?,
+        );
+        Ok(FeedReading { value, updated_at })
+    }
+}
+
+/// Amount raised as reported by a fundraising feed
+pub struct FeedReading {
+    pub value: u64,
+    pub updated_at: i64,
+}
+
+/// Indie.fun campaign account a market is bound to
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct CampaignBinding {
+    pub campaign: Pubkey,
+    /// Program that must own the campaign account
+    pub program: Pubkey,
+}
+
+impl CampaignBinding {
+    pub const LEN: usize = 32 + // campaign
+        32; // program
+
+    /// Reads `campaign`, checking its address, owner and account discriminator
+    pub fn read(&self, campaign: &AccountInfo) -> Result<CampaignData> {
+        require_keys_eq!(campaign.key(), self.campaign, ErrorCode::CampaignMismatch);
+        require_keys_eq!(*campaign.owner, self.program, ErrorCode::CampaignMismatch);
+
+        let data = campaign.try_borrow_data()?;
+        let discriminator = &hash(b"account:Campaign").to_bytes()[..8];
+        require!(
+            data.len() >= 8 && &data[..8] == discriminator,
+            ErrorCode::InvalidCampaignData
+        );
+        CampaignData::deserialize(&mut &data[8..])
+            .map_err(|_| ErrorCode::InvalidCampaignData.into())
+    }
+}
+
+/// Layout of an Indie.fun campaign account, after its 8-byte discriminator
+#[derive(AnchorDeserialize, Clone, Copy, Debug)]
+pub struct CampaignData {
+    pub creator: Pubkey,
+    pub goal: u64,
+    pub total_raised: u64,
+    pub end_ts: i64,
+    pub status: CampaignStatus,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CampaignStatus {
+    Active,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+/// Settlement details kept for audits and dispute handling
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct SettlementRecord {
+    /// Amount raised as reported at settlement; zero for voided markets
+    pub fundraising_result: u64,
+    pub settled_at: i64,
+    pub settler: Pubkey,
+    /// Hash of the data the result was taken from
+    pub evidence_hash: [u8; 32],
+    /// Where that data can be found
+    pub evidence_uri: String,
+}
+
+impl SettlementRecord {
+    pub const LEN: usize = 8 + // fundraising_result
+        8 +  // settled_at
+        32 + // settler
+        32 + // evidence_hash
+        4 + MAX_EVIDENCE_URI_LEN; // evidence_uri (4 byte prefix + max 200 chars)
+}
+
+/// Arguments to `initialize`; see its docs for how they fit together
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct InitializeParams {
+    pub fundraising_goal: u64,
+    pub trading_close_ts: i64,
+    pub campaign_end_ts: i64,
+    pub settlement_available_ts: i64,
+    pub project_name: String,
+    /// LMSR liquidity parameter; ignored in parimutuel mode
+    pub lmsr_b: u64,
+    pub fee_bps: u16,
+    pub fee_recipient: Pubkey,
+    pub lp_fee_bps: u16,
+    pub mode: MarketMode,
+    /// Empty for a binary market
+    pub bucket_bounds_bps: Vec<u32>,
+    pub scalar_range: Option<ScalarRange>,
+    pub resolution_deadline: i64,
+    pub optimistic_oracle: Option<OptimisticOracleConfig>,
+    pub fundraising_feed: Option<FeedConfig>,
+    /// Owner program of the `campaign` account the market binds to
+    pub campaign_program: Option<Pubkey>,
+    /// Bump of each outcome mint PDA, in outcome order
+    pub outcome_mint_bumps: Vec<u8>,
+}
+
+/// Fundraising amounts a scalar market's LONG token pays nothing at and pays in full at
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct ScalarRange {
+    pub lower_bound: u64,
+    pub upper_bound: u64,
+}
+
+/// How a market prices trades and pays winners
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum MarketMode {
+    /// LMSR market maker; tokens trade both ways and winners redeem 1:1
+    Lmsr,
+    /// Pooled stakes minted 1:1, no selling; winners split the pot pro rata
+    Parimutuel,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum TradeSide {
+    Buy,
+    Sell,
+}
+
+/// Amounts actually exchanged by a trade instruction
+/// `usdc` is what the user paid (buys) or received (sells), with `fee` already included
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub struct TradeReceipt {
+    pub tokens: u64,
+    pub usdc: u64,
+    pub fee: u64,
 }
 
+/// Result of the `quote` instruction
+/// Prices are USDC per token scaled by `PRICE_SCALE`
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
-pub enum Outcome {
-    Yes,
-    No,
+pub struct Quote {
+    /// Tokens minted to the trader (buys only)
+    pub tokens_out: u64,
+    /// USDC paid to the trader (sells only)
+    pub usdc_out: u64,
+    /// USDC per token actually paid or received
+    pub average_price: u64,
+    /// Price of the traded outcome once the trade has moved the market
+    /// (parimutuel: the outcome's share of all stakes)
+    pub marginal_price: u64,
+    /// Difference between the average price and the pre-trade price, in bps of the latter
+    pub price_impact_bps: u64,
+    /// Trading fee included in the amounts above
+    pub fee: u64,
+}
+
+#[event]
+pub struct MarketPaused {
+    pub market: Pubkey,
+    /// Incident code given by the authority
+    pub reason: u16,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MarketResumed {
+    pub market: Pubkey,
+    pub reason: u16,
+    pub timestamp: i64,
 }
 
 #[error_code]
 pub enum ErrorCode {
-    #[msg("Invalid deadline - must be in the future")]
+    #[msg("Invalid trading close time - must be in the future")]
     InvalidDeadline,
     #[msg("Market has already been settled")]
     MarketSettled,
-    #[msg("Market deadline has passed")]
+    #[msg("Trading has closed for this market")]
     DeadlinePassed,
-    #[msg("Market deadline has not passed yet")]
+    #[msg("Settlement is not available yet")]
     DeadlineNotPassed,
     #[msg("Market has already been settled")]
     MarketAlreadySettled,
@@ -647,4 +4053,118 @@ pub enum ErrorCode {
     Unauthorized,
     #[msg("Math overflow occurred")]
     MathOverflow,
+    #[msg("LMSR liquidity parameter b must be greater than zero")]
+    InvalidLiquidityParameter,
+    #[msg("Trade amount is too small to mint any tokens")]
+    TradeTooSmall,
+    #[msg("Trade output is below the requested minimum")]
+    SlippageExceeded,
+    #[msg("Transaction expired before it was processed")]
+    TransactionExpired,
+    #[msg("Fee exceeds the maximum allowed")]
+    InvalidFee,
+    #[msg("No fees to claim")]
+    NoFeesToClaim,
+    #[msg("Collateral vault would not cover every redeemable token")]
+    VaultInsolvent,
+    #[msg("Selling is disabled in parimutuel markets")]
+    SellingDisabled,
+    #[msg("Instruction is not available in this market mode")]
+    UnsupportedMarketMode,
+    #[msg("Project name exceeds the maximum length")]
+    ProjectNameTooLong,
+    #[msg("Outcome mint does not match the market")]
+    InvalidOutcomeMint,
+    #[msg("Outcome does not exist in this market")]
+    InvalidOutcome,
+    #[msg("Bucket bounds must be strictly increasing, with fewer than the maximum outcomes")]
+    InvalidOutcomeBuckets,
+    #[msg("Scalar lower bound must be below the upper bound")]
+    InvalidScalarRange,
+    #[msg("Token account does not hold the expected outcome token")]
+    OutcomeMintMismatch,
+    #[msg("Token account is not denominated in the market's collateral mint")]
+    CollateralMintMismatch,
+    #[msg("LP mint or LP token account does not match the market")]
+    LpMintMismatch,
+    #[msg("Token account is not owned by the signer")]
+    TokenAccountOwnerMismatch,
+    #[msg("Only the authority can void a market before the resolution deadline")]
+    ResolutionDeadlineNotPassed,
+    #[msg("Resolution deadline has passed - the market can only be voided")]
+    ResolutionDeadlinePassed,
+    #[msg("Resolution deadline must be after settlement opens")]
+    InvalidResolutionDeadline,
+    #[msg("Evidence URI exceeds the maximum length")]
+    EvidenceUriTooLong,
+    #[msg("Account is not a market")]
+    AccountDiscriminatorMismatch,
+    #[msg("Optimistic oracle bond and dispute window must be positive")]
+    InvalidOracleConfig,
+    #[msg("Market does not use the optimistic oracle")]
+    OptimisticOracleDisabled,
+    #[msg("Proposal has already been disputed")]
+    ProposalAlreadyDisputed,
+    #[msg("Proposal has not been disputed")]
+    ProposalNotDisputed,
+    #[msg("Dispute window has closed")]
+    DisputeWindowClosed,
+    #[msg("Dispute window is still open")]
+    DisputeWindowOpen,
+    #[msg("No bond to reclaim")]
+    NoBondToReclaim,
+    #[msg("Resolver committee needs 1 to 10 distinct resolvers and a threshold no larger than their number")]
+    InvalidResolverCommittee,
+    #[msg("Market is settled by its resolver committee")]
+    ResolverCommitteeRequired,
+    #[msg("Resolver has already voted")]
+    AlreadyVoted,
+    #[msg("Feed max staleness must be positive")]
+    InvalidFeedConfig,
+    #[msg("Market has no fundraising feed")]
+    FundraisingFeedDisabled,
+    #[msg("Feed account or its owner program does not match the market")]
+    FeedMismatch,
+    #[msg("Feed is not published by the expected publisher")]
+    FeedPublisherMismatch,
+    #[msg("Feed data is too short for the configured layout")]
+    InvalidFeedData,
+    #[msg("Feed reading is stale")]
+    StaleFeed,
+    #[msg("Campaign account or its owner program does not match the market")]
+    CampaignMismatch,
+    #[msg("Account is not a campaign")]
+    InvalidCampaignData,
+    #[msg("Fundraising goal does not match the campaign's goal")]
+    CampaignGoalMismatch,
+    #[msg("Campaign has not succeeded or failed yet")]
+    CampaignNotFinished,
+    #[msg("Campaign was cancelled - the market can only be voided")]
+    CampaignCancelled,
+    #[msg("Market settles from its campaign account")]
+    CampaignBound,
+    #[msg("Only binary markets can be settled early")]
+    UnsupportedMarketKind,
+    #[msg("Account is not the market's campaign or fundraising feed")]
+    UntrustedSource,
+    #[msg("Fundraising goal has not been reached")]
+    GoalNotReached,
+    #[msg("Trading must close by the campaign end, and settlement open no earlier")]
+    InvalidMarketSchedule,
+    #[msg("Market is paused")]
+    MarketPaused,
+    #[msg("Market is not paused")]
+    MarketNotPaused,
+    #[msg("Campaign end time does not match the campaign's end")]
+    CampaignEndMismatch,
+    #[msg("Market account predates the layouts resize_market can grow")]
+    UnsupportedMarketLayout,
+    #[msg("Market settles from its fundraising feed")]
+    FeedBound,
+    #[msg("Market settles through its optimistic oracle")]
+    OracleBound,
+    #[msg("Trading is still open - pause the market before voiding it")]
+    TradingStillOpen,
+    #[msg("Account is not a market in the original market_v2 layout")]
+    InvalidLegacyMarket,
 }
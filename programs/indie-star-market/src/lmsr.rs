@@ -0,0 +1,462 @@
+//! Fixed-point math for the LMSR (logarithmic market scoring rule) market maker.
+//!
+//! The cost function is `C(q) = b * ln(sum_i exp(q_i / b))` and the price of
+//! outcome `i` is `exp(q_i / b) / sum_j exp(q_j / b)`, so prices always sum to
+//! one and the market maker can lose at most `b * ln(n)` over `n` outcomes.
+//!
+//! Quantities, `b` and collateral amounts are all in token base units.
+//! Intermediate values are 18-decimal fixed point (`WAD`), and every exponent
+//! is taken relative to the largest quantity so `exp` is only ever evaluated
+//! on non-positive arguments.
+//!
+//! Rounding always favors the pool: costs are rounded up, proceeds and token
+//! amounts bought with a fixed budget are rounded down.
+
+use anchor_lang::prelude::*;
+
+use crate::ErrorCode;
+
+/// Fixed-point scale used for intermediate values (1.0 == `WAD`).
+pub const WAD: u128 = 1_000_000_000_000_000_000;
+
+/// ln(2) as a WAD.
+const LN_2: u128 = 693_147_180_559_945_309;
+
+/// exp(-x) is below one WAD unit past this point.
+const EXP_NEG_CUTOFF: u128 = 42 * WAD;
+
+/// Returns `exp(-x)` for a non-negative WAD `x`, as a WAD.
+fn exp_neg(x: u128) -> u128 {
+    if x >= EXP_NEG_CUTOFF {
+        return 0;
+    }
+
+    // exp(-x) = 2^-k * exp(-r) with r in [0, ln 2)
+    let k = x / LN_2;
+    let r = x % LN_2;
+
+    // exp(r) by Taylor series; every term is positive so there is no
+    // cancellation, and r < 1 keeps the products well inside u128.
+    let mut term = WAD;
+    let mut sum = WAD;
+    let mut n = 1u128;
+    while term > 0 {
+        term = term * r / (n * WAD);
+        sum += term;
+        n += 1;
+    }
+
+    (WAD * WAD / sum) >> k
+}
+
+/// Returns `ln(x)` for a WAD `x >= 1.0`, as a WAD.
+fn ln_ge_one(x: u128) -> u128 {
+    // x = m * 2^k with m in [1, 2)
+    let k = (x / WAD).ilog2();
+    let m = x >> k;
+
+    // ln(m) = 2 * atanh(z) with z = (m - 1) / (m + 1) in [0, 1/3)
+    let z = (m - WAD) * WAD / (m + WAD);
+    let z2 = z * z / WAD;
+    let mut term = z;
+    let mut sum = 0u128;
+    let mut n = 1u128;
+    while term > 0 {
+        sum += term / n;
+        term = term * z2 / WAD;
+        n += 2;
+    }
+
+    k as u128 * LN_2 + 2 * sum
+}
+
+/// Returns `ln(x)` for a positive WAD `x`, as a signed WAD.
+fn ln(x: u128) -> Result<i128> {
+    require!(x > 0, ErrorCode::MathOverflow);
+    if x >= WAD {
+        Ok(ln_ge_one(x) as i128)
+    } else {
+        Ok(-(ln_ge_one(WAD * WAD / x) as i128))
+    }
+}
+
+/// Snapshot of the exponential weights of a quantity vector.
+struct Weights {
+    /// Largest quantity; every weight is relative to it.
+    max: u64,
+    /// `exp((q_i - max) / b)` for each outcome, as WADs.
+    each: Vec<u128>,
+    /// Sum of `each`; at least 1.0 because the largest outcome weighs 1.0.
+    sum: u128,
+}
+
+fn weights(quantities: &[u64], b: u64) -> Result<Weights> {
+    require!(b > 0, ErrorCode::InvalidLiquidityParameter);
+    let max = quantities.iter().copied().max().unwrap_or(0);
+
+    let mut each = Vec::with_capacity(quantities.len());
+    let mut sum = 0u128;
+    for &q in quantities {
+        let exponent = ((max - q) as u128)
+            .checked_mul(WAD)
+            .ok_or(ErrorCode::MathOverflow)?
+            / b as u128;
+        let w = exp_neg(exponent);
+        sum += w;
+        each.push(w);
+    }
+
+    Ok(Weights { max, each, sum })
+}
+
+/// Value of the cost function in WAD-scaled base units.
+fn cost(quantities: &[u64], b: u64) -> Result<u128> {
+    let w = weights(quantities, b)?;
+    (w.max as u128)
+        .checked_mul(WAD)
+        .and_then(|base| base.checked_add((b as u128).checked_mul(ln_ge_one(w.sum))?))
+        .ok_or(ErrorCode::MathOverflow.into())
+}
+
+/// Quantity vector after `outcome` moves by `delta` (signed), re-based so
+/// its smallest entry is zero, along with the amount it was shifted down by.
+///
+/// Prices and cost differences only depend on the gaps between quantities,
+/// so the shift is free. It lets holders of split tokens sell more of an
+/// outcome than the market maker itself has issued.
+fn moved(quantities: &[u64], outcome: usize, delta: i128) -> Result<(Vec<u64>, i128)> {
+    let mut signed: Vec<i128> = quantities.iter().map(|&q| q as i128).collect();
+    signed[outcome] = signed[outcome]
+        .checked_add(delta)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let min = signed.iter().copied().min().unwrap_or(0);
+    let rebased = signed
+        .into_iter()
+        .map(|q| u64::try_from(q - min).map_err(|_| ErrorCode::MathOverflow.into()))
+        .collect::<Result<Vec<u64>>>()?;
+    Ok((rebased, min))
+}
+
+/// Change in the cost function when `outcome` moves by `delta` (signed), in
+/// WAD-scaled base units.
+fn cost_change(quantities: &[u64], b: u64, outcome: usize, delta: i128) -> Result<i128> {
+    let before = cost(quantities, b)? as i128;
+    let (after, shift) = moved(quantities, outcome, delta)?;
+    // C(q + s) = C(q) + s, so add the shift back to compare like with like
+    (cost(&after, b)? as i128)
+        .checked_add(shift.checked_mul(WAD as i128).ok_or(ErrorCode::MathOverflow)?)
+        .and_then(|a| a.checked_sub(before))
+        .ok_or(ErrorCode::MathOverflow.into())
+}
+
+/// Applies a trade of `delta` tokens of `outcome` to the stored quantities.
+pub fn apply_trade(quantities: &mut [u64], outcome: usize, delta: i128) -> Result<()> {
+    let (updated, _) = moved(quantities, outcome, delta)?;
+    quantities.copy_from_slice(&updated);
+    Ok(())
+}
+
+/// Collateral the market maker must hold up front to cover its worst-case
+/// loss, `b * ln(n)`, rounded up.
+pub fn max_subsidy(b: u64, outcomes: usize) -> Result<u64> {
+    require!(b > 0, ErrorCode::InvalidLiquidityParameter);
+    let loss = (b as u128)
+        .checked_mul(ln_ge_one(outcomes as u128 * WAD))
+        .ok_or(ErrorCode::MathOverflow)?;
+    u64::try_from(loss.div_ceil(WAD)).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+/// Value of the cost function in base units, rounded up.
+pub fn total_cost(quantities: &[u64], b: u64) -> Result<u64> {
+    u64::try_from(cost(quantities, b)?.div_ceil(WAD)).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+/// Moves the market to liquidity parameter `new_b` without moving prices by
+/// scaling every quantity by `new_b / b`, rounding down.
+///
+/// `C(k * q; k * b) = k * C(q; b)`, so this also scales the market maker's
+/// remaining worst-case loss by the same factor.
+pub fn rescale(quantities: &mut [u64], b: u64, new_b: u64) -> Result<()> {
+    require!(b > 0 && new_b > 0, ErrorCode::InvalidLiquidityParameter);
+    for q in quantities.iter_mut() {
+        let scaled = (*q as u128)
+            .checked_mul(new_b as u128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / b as u128;
+        *q = u64::try_from(scaled).map_err(|_| ErrorCode::MathOverflow)?;
+    }
+    Ok(())
+}
+
+/// Instantaneous price of `outcome` as a WAD; prices across outcomes sum to 1.0.
+pub fn price(quantities: &[u64], b: u64, outcome: usize) -> Result<u128> {
+    let w = weights(quantities, b)?;
+    Ok(w.each[outcome] * WAD / w.sum)
+}
+
+/// Collateral charged for minting `amount` tokens of `outcome`, rounded up.
+pub fn cost_to_buy(quantities: &[u64], b: u64, outcome: usize, amount: u64) -> Result<u64> {
+    let diff = cost_change(quantities, b, outcome, amount as i128)?.max(0) as u128;
+    u64::try_from(diff.div_ceil(WAD)).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+/// Collateral paid out for burning `amount` tokens of `outcome`, rounded down.
+pub fn proceeds_from_sell(
+    quantities: &[u64],
+    b: u64,
+    outcome: usize,
+    amount: u64,
+) -> Result<u64> {
+    let diff = (-cost_change(quantities, b, outcome, -(amount as i128))?).max(0) as u128;
+    u64::try_from(diff / WAD).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+/// Largest `x` for which `fits(x)` holds, given a close `estimate`.
+///
+/// `fits` must hold at zero and flip to false exactly once. The closed-form
+/// estimates are usually exact or off by one, but lose precision when a trade
+/// nearly exhausts the curve, so the boundary is bracketed by doubling steps
+/// away from the estimate and then bisected.
+fn largest_fitting(estimate: u64, fits: impl Fn(u64) -> Result<bool>) -> Result<u64> {
+    let (mut lo, mut hi);
+    let mut step = 1u64;
+    if fits(estimate)? {
+        lo = estimate;
+        loop {
+            let probe = lo.checked_add(step).ok_or(ErrorCode::MathOverflow)?;
+            if !fits(probe)? {
+                hi = probe;
+                break;
+            }
+            lo = probe;
+            step = step.saturating_mul(2);
+        }
+    } else {
+        hi = estimate;
+        loop {
+            let probe = hi.saturating_sub(step);
+            if probe == 0 || fits(probe)? {
+                lo = probe;
+                break;
+            }
+            hi = probe;
+            step = step.saturating_mul(2);
+        }
+    }
+
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        if fits(mid)? {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    Ok(lo)
+}
+
+/// Largest number of `outcome` tokens that `budget` collateral buys.
+///
+/// Solves `C(q + x * e_i) - C(q) = budget` in closed form,
+///
+/// `x = (max - q_i) + budget + b * ln(S - (S - w_i) * exp(-budget / b))`,
+///
+/// where `S` and `w_i` are the weights relative to the largest quantity, then
+/// settles on the largest amount whose rounded-up cost fits the budget.
+pub fn tokens_for_collateral(
+    quantities: &[u64],
+    b: u64,
+    outcome: usize,
+    budget: u64,
+) -> Result<u64> {
+    if budget == 0 {
+        return Ok(0);
+    }
+
+    let w = weights(quantities, b)?;
+    let scaled_budget = (budget as u128)
+        .checked_mul(WAD)
+        .ok_or(ErrorCode::MathOverflow)?
+        / b as u128;
+    let rest = w.sum - w.each[outcome];
+    let inner = w.sum - rest * exp_neg(scaled_budget) / WAD;
+
+    let log_term = (scaled_budget as i128)
+        .checked_add(ln(inner)?)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let gap = (w.max - quantities[outcome]) as i128;
+    let estimate = (b as i128)
+        .checked_mul(log_term)
+        .and_then(|v| v.checked_add(gap.checked_mul(WAD as i128)?))
+        .ok_or(ErrorCode::MathOverflow)?
+        / WAD as i128;
+    let estimate = u64::try_from(estimate.max(0)).map_err(|_| ErrorCode::MathOverflow)?;
+
+    largest_fitting(estimate, |tokens| {
+        Ok(cost_to_buy(quantities, b, outcome, tokens)? <= budget)
+    })
+}
+
+/// Smallest number of `outcome` tokens whose sale pays out at least `target`.
+///
+/// Solves `C(q) - C(q - x * e_i) = target` in closed form,
+///
+/// `x = -(max - q_i) - b * ln(w_i - S * (1 - exp(-target / b)))`,
+///
+/// and fails if the logarithm's argument is not positive: selling any amount
+/// of `outcome` pays strictly less than `b * ln(S / (S - w_i))`.
+pub fn tokens_for_proceeds(
+    quantities: &[u64],
+    b: u64,
+    outcome: usize,
+    target: u64,
+) -> Result<u64> {
+    if target == 0 {
+        return Ok(0);
+    }
+
+    let w = weights(quantities, b)?;
+    let scaled_target = (target as u128)
+        .checked_mul(WAD)
+        .ok_or(ErrorCode::MathOverflow)?
+        / b as u128;
+    let spent = w.sum * (WAD - exp_neg(scaled_target)) / WAD;
+    require!(
+        w.each[outcome] > spent,
+        ErrorCode::InsufficientLiquidity
+    );
+
+    let gap = (w.max - quantities[outcome]) as i128;
+    let estimate = (b as i128)
+        .checked_mul(-ln(w.each[outcome] - spent)?)
+        .and_then(|v| v.checked_sub(gap.checked_mul(WAD as i128)?))
+        .ok_or(ErrorCode::MathOverflow)?
+        / WAD as i128;
+    let estimate = u64::try_from(estimate.max(0)).map_err(|_| ErrorCode::MathOverflow)?;
+
+    let short_of_target =
+        |tokens| Ok(proceeds_from_sell(quantities, b, outcome, tokens)? < target);
+    Ok(largest_fitting(estimate, short_of_target)? + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const B: u64 = 1_000_000_000;
+
+    fn assert_close(actual: u128, expected: u128, tolerance: u128) {
+        assert!(
+            actual.abs_diff(expected) <= tolerance,
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    #[test]
+    fn exp_and_ln_match_known_values() {
+        assert_eq!(exp_neg(0), WAD);
+        assert_close(exp_neg(LN_2), WAD / 2, 10);
+        assert_close(exp_neg(WAD), 367_879_441_171_442_321, 1_000);
+        assert_eq!(exp_neg(EXP_NEG_CUTOFF), 0);
+
+        assert_eq!(ln_ge_one(WAD), 0);
+        assert_close(ln_ge_one(2 * WAD), LN_2, 10);
+        assert_close(ln_ge_one(10 * WAD), 2_302_585_092_994_045_684, 1_000);
+        assert_close((-ln(WAD / 2).unwrap()) as u128, LN_2, 10);
+    }
+
+    #[test]
+    fn prices_sum_to_one() {
+        let markets: [&[u64]; 4] = [
+            &[0, 0],
+            &[0, 3 * B],
+            &[7 * B, 0, B / 2],
+            &[0, 1, 2 * B, 40 * B, 5, 0, 12_345, B],
+        ];
+        for quantities in markets {
+            let total: u128 = (0..quantities.len())
+                .map(|outcome| price(quantities, B, outcome).unwrap())
+                .sum();
+            // Each price rounds down, by less than a unit
+            assert!(total <= WAD);
+            assert!(total + quantities.len() as u128 >= WAD);
+        }
+        assert_eq!(price(&[0, 0], B, 0).unwrap(), WAD / 2);
+    }
+
+    #[test]
+    fn tokens_for_collateral_is_the_largest_amount_cost_to_buy_allows() {
+        let quantities = [0, 2 * B, B / 3];
+        for outcome in 0..quantities.len() {
+            for budget in [1, 999, B / 10, B, 25 * B] {
+                let tokens = tokens_for_collateral(&quantities, B, outcome, budget).unwrap();
+                assert!(cost_to_buy(&quantities, B, outcome, tokens).unwrap() <= budget);
+                assert!(cost_to_buy(&quantities, B, outcome, tokens + 1).unwrap() > budget);
+            }
+        }
+        assert_eq!(tokens_for_collateral(&quantities, B, 0, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn tokens_for_proceeds_is_the_smallest_amount_that_pays_the_target() {
+        let quantities = [B, 0];
+        for target in [1, 1_000, B / 10, B / 3] {
+            let tokens = tokens_for_proceeds(&quantities, B, 0, target).unwrap();
+            assert!(proceeds_from_sell(&quantities, B, 0, tokens).unwrap() >= target);
+            assert!(proceeds_from_sell(&quantities, B, 0, tokens - 1).unwrap() < target);
+        }
+        // Selling never pays more than b * ln(S / (S - w_i))
+        assert!(tokens_for_proceeds(&[0, 0], B, 0, B).is_err());
+    }
+
+    #[test]
+    fn buying_then_selling_never_pays_out_more_than_it_took() {
+        let mut quantities = vec![0, B / 2, 0];
+        let cost = cost_to_buy(&quantities, B, 1, 3 * B).unwrap();
+        apply_trade(&mut quantities, 1, 3 * B as i128).unwrap();
+        let proceeds = proceeds_from_sell(&quantities, B, 1, 3 * B).unwrap();
+        assert!(proceeds <= cost);
+        assert!(cost - proceeds <= 2);
+    }
+
+    #[test]
+    fn max_subsidy_is_b_ln_n_rounded_up() {
+        assert_eq!(max_subsidy(B, 2).unwrap(), 693_147_181);
+        assert_eq!(max_subsidy(B, 8).unwrap(), 2_079_441_542);
+        assert_eq!(max_subsidy(1, 2).unwrap(), 1);
+        assert!(max_subsidy(0, 2).is_err());
+    }
+
+    #[test]
+    fn max_subsidy_covers_the_winning_outcome_after_any_buys() {
+        for outcomes in [2usize, 3, 8] {
+            let subsidy = max_subsidy(B, outcomes).unwrap() as u128;
+            let mut quantities = vec![0u64; outcomes];
+            let mut issued = vec![0u128; outcomes];
+            let mut collected = 0u128;
+
+            // Pile into one outcome, then spread smaller buys over the rest
+            let trades = [(0, 50 * B), (1, B), (outcomes - 1, 3 * B), (0, 7 * B)];
+            for (outcome, amount) in trades {
+                collected += cost_to_buy(&quantities, B, outcome, amount).unwrap() as u128;
+                apply_trade(&mut quantities, outcome, amount as i128).unwrap();
+                issued[outcome] += amount as u128;
+            }
+
+            let worst_payout = issued.iter().copied().max().unwrap();
+            assert!(subsidy + collected >= worst_payout);
+        }
+    }
+
+    #[test]
+    fn rescale_keeps_prices_and_scales_the_cost() {
+        let mut quantities = vec![3 * B, 0];
+        let before = price(&quantities, B, 0).unwrap();
+        let cost = total_cost(&quantities, B).unwrap();
+        rescale(&mut quantities, B, 2 * B).unwrap();
+        assert_eq!(quantities, vec![6 * B, 0]);
+        assert_eq!(price(&quantities, 2 * B, 0).unwrap(), before);
+        assert_close(total_cost(&quantities, 2 * B).unwrap() as u128, 2 * cost as u128, 2);
+    }
+}
@@ -0,0 +1,81 @@
+//! Local stand-in for an Indie.fun campaign program, used in tests.
+//!
+//! A `Campaign` account has the same layout as an Indie.fun campaign: creator,
+//! goal, amount raised, end time and status. Tests drive it through these
+//! instructions so a market's goal and result come from the same account.
+
+use anchor_lang::prelude::*;
+
+declare_id!("BzxToJTwmQS2JcNJXqS6VYN4jDpCjHd6NxpKDntb6aa");
+
+#[program]
+pub mod mock_campaign {
+    use super::*;
+
+    /// Create an active campaign run by the signer
+    pub fn create_campaign(ctx: Context<CreateCampaign>, goal: u64, end_ts: i64) -> Result<()> {
+        let campaign = &mut ctx.accounts.campaign;
+        campaign.creator = ctx.accounts.creator.key();
+        campaign.goal = goal;
+        campaign.total_raised = 0;
+        campaign.end_ts = end_ts;
+        campaign.status = CampaignStatus::Active;
+        Ok(())
+    }
+
+    /// Set the amount raised so far (creator only)
+    pub fn set_raised(ctx: Context<UpdateCampaign>, total_raised: u64) -> Result<()> {
+        ctx.accounts.campaign.total_raised = total_raised;
+        Ok(())
+    }
+
+    /// Move the campaign to `status` (creator only)
+    pub fn set_status(ctx: Context<UpdateCampaign>, status: CampaignStatus) -> Result<()> {
+        ctx.accounts.campaign.status = status;
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct CreateCampaign<'info> {
+    #[account(init, payer = creator, space = 8 + Campaign::LEN)]
+    pub campaign: Account<'info, Campaign>,
+
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCampaign<'info> {
+    #[account(mut, has_one = creator)]
+    pub campaign: Account<'info, Campaign>,
+
+    pub creator: Signer<'info>,
+}
+
+#[account]
+pub struct Campaign {
+    pub creator: Pubkey,
+    pub goal: u64,
+    pub total_raised: u64,
+    pub end_ts: i64,
+    pub status: CampaignStatus,
+}
+
+impl Campaign {
+    pub const LEN: usize = 32 + // creator
+        8 + // goal
+        8 + // total_raised
+        8 + // end_ts
+        1; // status
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CampaignStatus {
+    Active,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
@@ -0,0 +1,77 @@
+//! Local stand-in for a fundraising data feed, used in tests.
+//!
+//! A `FundraisingFeed` account holds the amount a campaign has raised, the time
+//! it was last updated and the publisher allowed to update it. Markets read it
+//! with `FundraisingFeed::PUBLISHER_OFFSET`, `VALUE_OFFSET` and
+//! `TIMESTAMP_OFFSET` as their feed layout.
+
+use anchor_lang::prelude::*;
+
+declare_id!("89Ui2J1U4LL2Ke76v1Nc6fL5XYAhNTwFfiAcoM33xmLN");
+
+#[program]
+pub mod mock_fundraising_feed {
+    use super::*;
+
+    /// Create a feed published by the signer, starting at `total_raised`
+    pub fn initialize_feed(ctx: Context<InitializeFeed>, total_raised: u64) -> Result<()> {
+        let feed = &mut ctx.accounts.feed;
+        feed.publisher = ctx.accounts.publisher.key();
+        feed.total_raised = total_raised;
+        feed.updated_at = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    /// Report a new raised amount (publisher only)
+    pub fn update_feed(ctx: Context<UpdateFeed>, total_raised: u64) -> Result<()> {
+        let feed = &mut ctx.accounts.feed;
+        feed.total_raised = total_raised;
+        feed.updated_at = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    /// Report a raised amount with an explicit update time, to exercise staleness checks
+    pub fn set_feed(ctx: Context<UpdateFeed>, total_raised: u64, updated_at: i64) -> Result<()> {
+        let feed = &mut ctx.accounts.feed;
+        feed.total_raised = total_raised;
+        feed.updated_at = updated_at;
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeFeed<'info> {
+    #[account(init, payer = publisher, space = 8 + FundraisingFeed::LEN)]
+    pub feed: Account<'info, FundraisingFeed>,
+
+    #[account(mut)]
+    pub publisher: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFeed<'info> {
+    #[account(mut, has_one = publisher)]
+    pub feed: Account<'info, FundraisingFeed>,
+
+    pub publisher: Signer<'info>,
+}
+
+#[account]
+pub struct FundraisingFeed {
+    pub publisher: Pubkey,
+    pub total_raised: u64,
+    pub updated_at: i64,
+}
+
+impl FundraisingFeed {
+    pub const LEN: usize = 32 + // publisher
+        8 + // total_raised
+        8; // updated_at
+
+    /// Byte offsets into the account data, after the 8-byte discriminator
+    pub const PUBLISHER_OFFSET: u16 = 8;
+    pub const VALUE_OFFSET: u16 = 40;
+    pub const TIMESTAMP_OFFSET: u16 = 48;
+}
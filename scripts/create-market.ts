@@ -1,11 +1,12 @@
 /**
  * Helper script to create a new prediction market
- * This script creates the YES/NO token mints and initializes the market
+ * This script initializes the market; the program creates the outcome token mints
  */
 
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { IndieStarMarket } from "../target/types/indie_star_market";
+import { createHash } from "crypto";
 import {
   Keypair,
   PublicKey,
@@ -20,10 +21,15 @@ import {
   createAssociatedTokenAccountInstruction,
   getAssociatedTokenAddress,
   ASSOCIATED_TOKEN_PROGRAM_ID,
-  createSetAuthorityInstruction,
-  AuthorityType,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
 } from "@solana/spl-token";
 
+// LMSR liquidity parameter for new markets, 1000 USDC
+const LMSR_B = 1_000_000_000;
+// Trading fee in basis points (1%), paid to the market authority
+const FEE_BPS = 100;
+
 async function createMarket() {
   // Set up provider
   // For local testing: ensure solana-test-validator is running
@@ -144,60 +150,8 @@ async function createMarket() {
     console.log("Mock USDC mint:", usdcMint.toString());
   }
 
-  // Step 1: Create YES token mint
-  console.log("\n1. Creating YES token mint...");
-  const yesMint = Keypair.generate();
-  const yesMintRent = await getMinimumBalanceForRentExemptMint(
-    provider.connection
-  );
-
-  const createYesMintTx = new anchor.web3.Transaction().add(
-    anchor.web3.SystemProgram.createAccount({
-      fromPubkey: authority.publicKey,
-      newAccountPubkey: yesMint.publicKey,
-      space: MINT_SIZE,
-      lamports: yesMintRent,
-      programId: TOKEN_PROGRAM_ID,
-    }),
-    createInitializeMintInstruction(
-      yesMint.publicKey,
-      9, // decimals
-      authority.publicKey, // mint authority (can be changed to market PDA)
-      null // freeze authority
-    )
-  );
-
-  await provider.sendAndConfirm(createYesMintTx, [authority.payer, yesMint]);
-  console.log("YES mint:", yesMint.publicKey.toString());
-
-  // Step 2: Create NO token mint
-  console.log("\n2. Creating NO token mint...");
-  const noMint = Keypair.generate();
-  const noMintRent = await getMinimumBalanceForRentExemptMint(
-    provider.connection
-  );
-
-  const createNoMintTx = new anchor.web3.Transaction().add(
-    anchor.web3.SystemProgram.createAccount({
-      fromPubkey: authority.publicKey,
-      newAccountPubkey: noMint.publicKey,
-      space: MINT_SIZE,
-      lamports: noMintRent,
-      programId: TOKEN_PROGRAM_ID,
-    }),
-    createInitializeMintInstruction(
-      noMint.publicKey,
-      9, // decimals
-      authority.publicKey, // mint authority
-      null
-    )
-  );
-
-  await provider.sendAndConfirm(createNoMintTx, [authority.payer, noMint]);
-  console.log("NO mint:", noMint.publicKey.toString());
-
-  // Step 3: Check if program is deployed
-  console.log("\n3. Checking if program is deployed...");
+  // Step 1: Check if program is deployed
+  console.log("\n1. Checking if program is deployed...");
   try {
     const programInfo = await provider.connection.getAccountInfo(program.programId);
     if (!programInfo) {
@@ -215,82 +169,130 @@ async function createMarket() {
     process.exit(1);
   }
 
-  // Step 4: Initialize market (Anchor will automatically derive the market PDA)
-  console.log("\n4. Initializing market...");
+  // Step 2: Make sure the program config exists
+  console.log("\n2. Checking program config...");
+  const [configPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("config")],
+    program.programId
+  );
+  let config = await program.account.programConfig.fetchNullable(configPda);
+  if (!config) {
+    // Only the program's upgrade authority can create the config
+    const [programData] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+    await program.methods
+      .initializeConfig(0, authority.publicKey)
+      .accountsPartial({
+        config: configPda,
+        admin: authority.publicKey,
+        program: program.programId,
+        programData,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+    config = await program.account.programConfig.fetch(configPda);
+    console.log("Program config created");
+  }
+
+  // Step 3: Initialize market
+  // The program creates the outcome mints at PDAs derived from the market
+  console.log("\n3. Initializing market...");
 
   // Derive market PDA for display  // Derive Market PDA
   const [marketPda] = PublicKey.findProgramAddressSync(
     [
       Buffer.from("market_v2"),
       authority.publicKey.toBuffer(),
-      Buffer.from(projectName),
+      createHash("sha256").update(projectName).digest(),
     ],
     program.programId
   );
   console.log("Market PDA:", marketPda.toString());
+  const outcomeMintPdas = ["yes", "no"].map((seed) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("mint"), marketPda.toBuffer(), Buffer.from(seed)],
+      program.programId
+    )
+  );
+  const outcomeMints = outcomeMintPdas.map(([mint]) => mint);
+
+  // The new market is appended to the registry page its id falls in
+  const page = Buffer.alloc(8);
+  page.writeUInt32LE(Math.floor(config.marketCount.toNumber() / 64), 0);
+  const [registryPage] = PublicKey.findProgramAddressSync(
+    [Buffer.from("registry"), page],
+    program.programId
+  );
+
+  // The authority funds the LMSR subsidy, b * ln 2 for a binary market
+  const authorityUsdcAccount = await getOrCreateAssociatedTokenAccount(
+    provider.connection,
+    authority.payer,
+    usdcMint,
+    authority.publicKey
+  );
+  if (isLocalnet) {
+    await mintTo(
+      provider.connection,
+      authority.payer,
+      usdcMint,
+      authorityUsdcAccount.address,
+      authority.payer,
+      LMSR_B
+    );
+  }
 
   try {
     const tx = await program.methods
-      .initialize(
-        new anchor.BN(fundraisingGoal),
-        new anchor.BN(deadline),
-        projectName
-      )
+      .initialize({
+        fundraisingGoal: new anchor.BN(fundraisingGoal),
+        tradingCloseTs: new anchor.BN(deadline),
+        campaignEndTs: new anchor.BN(deadline),
+        settlementAvailableTs: new anchor.BN(deadline),
+        projectName,
+        lmsrB: new anchor.BN(LMSR_B),
+        feeBps: FEE_BPS,
+        feeRecipient: authority.publicKey,
+        lpFeeBps: 0,
+        mode: { lmsr: {} },
+        bucketBoundsBps: [],
+        scalarRange: null,
+        // The authority has a week after the deadline to settle before anyone can void
+        resolutionDeadline: new anchor.BN(deadline + 86400 * 7),
+        optimisticOracle: null,
+        fundraisingFeed: null,
+        campaignProgram: null,
+        outcomeMintBumps: outcomeMintPdas.map(([, bump]) => bump),
+      })
       .accounts({
         authority: authority.publicKey,
-        yesMint: yesMint.publicKey,
-        noMint: noMint.publicKey,
+        config: configPda,
+        registryPage,
+        authorityUsdcAccount: authorityUsdcAccount.address,
         usdcMint: usdcMint,
-        yesLiquidityAccount: PublicKey.findProgramAddressSync(
-          [Buffer.from("liquidity"), marketPda.toBuffer(), Buffer.from("yes")],
-          program.programId
-        )[0],
-        noLiquidityAccount: PublicKey.findProgramAddressSync(
-          [Buffer.from("liquidity"), marketPda.toBuffer(), Buffer.from("no")],
-          program.programId
-        )[0],
         usdcLiquidityAccount: PublicKey.findProgramAddressSync(
           [Buffer.from("liquidity"), marketPda.toBuffer(), Buffer.from("usdc")],
           program.programId
         )[0],
+        campaign: null,
       } as any)
+      .remainingAccounts(
+        outcomeMints.map((pubkey) => ({ pubkey, isSigner: false, isWritable: true }))
+      )
       .rpc();
 
     console.log("Market initialized! Transaction:", tx);
 
-    // Transfer mint authority to Market PDA
-    console.log("\nTransferring mint authority to Market PDA...");
-
-    const transferYesAuthTx = new anchor.web3.Transaction().add(
-      createSetAuthorityInstruction(
-        yesMint.publicKey,
-        authority.publicKey,
-        AuthorityType.MintTokens,
-        marketPda
-      )
-    );
-    await provider.sendAndConfirm(transferYesAuthTx, [authority.payer]);
-    console.log("Transferred YES mint authority");
-
-    const transferNoAuthTx = new anchor.web3.Transaction().add(
-      createSetAuthorityInstruction(
-        noMint.publicKey,
-        authority.publicKey,
-        AuthorityType.MintTokens,
-        marketPda
-      )
-    );
-    await provider.sendAndConfirm(transferNoAuthTx, [authority.payer]);
-    console.log("Transferred NO mint authority");
-
     // Fetch market state
     const marketAccount = await program.account.marketState.fetch(marketPda);
     console.log("\nMarket State:");
     console.log("  Project:", marketAccount.projectName);
     console.log("  Goal:", marketAccount.fundraisingGoal.toString(), "USDC");
-    console.log("  Deadline:", new Date(marketAccount.deadline.toNumber() * 1000).toISOString());
-    console.log("  YES Mint:", marketAccount.yesMint.toString());
-    console.log("  NO Mint:", marketAccount.noMint.toString());
+    console.log("  Trading closes:", new Date(marketAccount.tradingCloseTs.toNumber() * 1000).toISOString());
+    console.log("  YES Mint:", marketAccount.outcomeMints[0].toString());
+    console.log("  NO Mint:", marketAccount.outcomeMints[1].toString());
     console.log("  Settled:", marketAccount.isSettled);
   } catch (err: any) {
     if (err.message?.includes("ECONNREFUSED")) {
@@ -316,9 +318,8 @@ async function createMarket() {
 
   console.log("\n✅ Market created successfully!");
   console.log("\nNext steps:");
-  console.log("1. Create liquidity token accounts (YES, NO, USDC)");
-  console.log("2. Users can now buy/sell tokens");
-  console.log("3. After deadline, call settleMarket()");
+  console.log("1. Users can now buy/sell tokens");
+  console.log("2. After deadline, call settleMarket() before the resolution deadline");
 }
 
 createMarket()
@@ -1,26 +1,25 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { IndieStarMarket } from "../target/types/indie_star_market";
-import {
-    Keypair,
-    PublicKey,
-    SystemProgram,
-    SYSVAR_RENT_PUBKEY,
-    LAMPORTS_PER_SOL,
-} from "@solana/web3.js";
-import {
-    TOKEN_PROGRAM_ID,
-    MINT_SIZE,
-    createInitializeMintInstruction,
-    getMinimumBalanceForRentExemptMint,
-    getAssociatedTokenAddress,
-    getOrCreateAssociatedTokenAccount,
-    mintTo,
-    getAccount,
-    createSetAuthorityInstruction,
-    AuthorityType,
-} from "@solana/spl-token";
+import { Keypair, PublicKey } from "@solana/web3.js";
 import { expect } from "chai";
+import {
+    YES,
+    buy,
+    chainTime,
+    createMarket,
+    createUsdcMint,
+    ensureConfig,
+    expectError,
+    fund,
+    parimutuelMode,
+    redeem,
+    sell,
+    settle,
+    tokenBalance,
+    usdcAccount,
+    waitUntil,
+} from "./helpers";
 
 describe("End-to-End Tests", () => {
     // Configure the client to use the local cluster.
@@ -32,805 +31,214 @@ describe("End-to-End Tests", () => {
 
     // Test accounts
     let authority: Keypair;
-    let yesMint: Keypair;
-    let noMint: Keypair;
-    let usdcMint: Keypair;
-    let marketPda: PublicKey;
-    let marketBump: number;
-    let projectName: string;
+    let usdcMint: PublicKey;
 
     // User accounts
     let user1: Keypair;
     let user2: Keypair;
+    let user1Usdc: PublicKey;
+    let user2Usdc: PublicKey;
+
+    const usdcAmount = 1_000_000_000; // 1000 USDC (6 decimals)
 
     before(async () => {
-        // Generate test accounts
         authority = Keypair.generate();
-        yesMint = Keypair.generate();
-        noMint = Keypair.generate();
-        usdcMint = Keypair.generate();
         user1 = Keypair.generate();
         user2 = Keypair.generate();
+        await fund(provider, authority, user1, user2);
+        await ensureConfig(program);
 
-        projectName = "E2E Test Project";
-
-        // Airdrop SOL to all accounts
-        const airdropSigs = await Promise.all([
-            provider.connection.requestAirdrop(
-                authority.publicKey,
-                10 * LAMPORTS_PER_SOL
-            ),
-            provider.connection.requestAirdrop(
-                user1.publicKey,
-                10 * LAMPORTS_PER_SOL
-            ),
-            provider.connection.requestAirdrop(
-                user2.publicKey,
-                10 * LAMPORTS_PER_SOL
-            ),
-        ]);
-        await Promise.all(
-            airdropSigs.map((sig) =>
-                provider.connection.confirmTransaction(sig)
-            )
-        );
-
-        // Create YES token mint (with authority as mint authority initially)
-        const yesMintRent = await getMinimumBalanceForRentExemptMint(
-            provider.connection
-        );
-        const createYesMintTx = new anchor.web3.Transaction().add(
-            anchor.web3.SystemProgram.createAccount({
-                fromPubkey: authority.publicKey,
-                newAccountPubkey: yesMint.publicKey,
-                space: MINT_SIZE,
-                lamports: yesMintRent,
-                programId: TOKEN_PROGRAM_ID,
-            }),
-            createInitializeMintInstruction(
-                yesMint.publicKey,
-                9,
-                authority.publicKey, // Will transfer to market PDA later
-                null
-            )
-        );
-        await provider.sendAndConfirm(createYesMintTx, [authority, yesMint]);
-
-        // Create NO token mint (with authority as mint authority initially)
-        const noMintRent = await getMinimumBalanceForRentExemptMint(
-            provider.connection
-        );
-        const createNoMintTx = new anchor.web3.Transaction().add(
-            anchor.web3.SystemProgram.createAccount({
-                fromPubkey: authority.publicKey,
-                newAccountPubkey: noMint.publicKey,
-                space: MINT_SIZE,
-                lamports: noMintRent,
-                programId: TOKEN_PROGRAM_ID,
-            }),
-            createInitializeMintInstruction(
-                noMint.publicKey,
-                9,
-                authority.publicKey, // Will transfer to market PDA later
-                null
-            )
-        );
-        await provider.sendAndConfirm(createNoMintTx, [authority, noMint]);
-
-        // Create USDC token mint
-        const usdcMintRent = await getMinimumBalanceForRentExemptMint(
-            provider.connection
-        );
-        const createUsdcMintTx = new anchor.web3.Transaction().add(
-            anchor.web3.SystemProgram.createAccount({
-                fromPubkey: authority.publicKey,
-                newAccountPubkey: usdcMint.publicKey,
-                space: MINT_SIZE,
-                lamports: usdcMintRent,
-                programId: TOKEN_PROGRAM_ID,
-            }),
-            createInitializeMintInstruction(
-                usdcMint.publicKey,
-                6, // USDC uses 6 decimals
-                authority.publicKey,
-                null
-            )
-        );
-        await provider.sendAndConfirm(createUsdcMintTx, [authority, usdcMint]);
-
-        // Derive market PDA (using market_v2 seed with project_name)
-        // We need this to transfer mint authority to it
-        [marketPda, marketBump] = PublicKey.findProgramAddressSync(
-            [
-                Buffer.from("market_v2"),
-                authority.publicKey.toBuffer(),
-                Buffer.from(projectName),
-            ],
-            program.programId
-        );
-
-        console.log("Market PDA:", marketPda.toString());
-        console.log("Market Bump:", marketBump);
-
-        // Transfer mint authority to Market PDA (required for program to mint tokens)
-        console.log("Transferring mint authority to Market PDA...");
-        const transferYesAuthTx = new anchor.web3.Transaction().add(
-            createSetAuthorityInstruction(
-                yesMint.publicKey,
-                authority.publicKey,
-                AuthorityType.MintTokens,
-                marketPda
-            )
-        );
-        await provider.sendAndConfirm(transferYesAuthTx, [authority]);
-        console.log("✅ Transferred YES mint authority to Market PDA");
-
-        const transferNoAuthTx = new anchor.web3.Transaction().add(
-            createSetAuthorityInstruction(
-                noMint.publicKey,
-                authority.publicKey,
-                AuthorityType.MintTokens,
-                marketPda
-            )
-        );
-        await provider.sendAndConfirm(transferNoAuthTx, [authority]);
-        console.log("✅ Transferred NO mint authority to Market PDA");
+        usdcMint = await createUsdcMint(provider, authority);
+        user1Usdc = await usdcAccount(provider, usdcMint, authority, user1, usdcAmount);
+        user2Usdc = await usdcAccount(provider, usdcMint, authority, user2, usdcAmount);
     });
 
     describe("Complete Market Lifecycle", () => {
         it("Should complete full market lifecycle: Initialize → Buy → Sell → Settle → Redeem", async () => {
-            const fundraisingGoal = new anchor.BN(1000000); // 1 USDC (with 6 decimals)
-            const deadline = new anchor.BN(Date.now() / 1000 + 86400 * 30); // 30 days from now
+            const now = await chainTime(provider);
+            const deadline = now + 10;
 
             // ============================================
             // STEP 1: Initialize Market
             // ============================================
-            console.log("\n=== STEP 1: Initializing Market ===");
-
-            const initTx = await program.methods
-                .initialize(fundraisingGoal, deadline, projectName)
-                .accounts({
-                    market: marketPda,
-                    authority: authority.publicKey,
-                    yesMint: yesMint.publicKey,
-                    noMint: noMint.publicKey,
-                    usdcMint: usdcMint.publicKey,
-                    tokenProgram: TOKEN_PROGRAM_ID,
-                    systemProgram: SystemProgram.programId,
-                    rent: SYSVAR_RENT_PUBKEY,
-                } as any)
-                .signers([authority])
-                .rpc();
-
-            console.log("Initialize transaction:", initTx);
-
-            // Verify market state
-            const marketAccount = await program.account.marketState.fetch(
-                marketPda
-            );
-            expect(marketAccount.authority.toString()).to.equal(
-                authority.publicKey.toString()
-            );
-            expect(marketAccount.fundraisingGoal.toNumber()).to.equal(
-                fundraisingGoal.toNumber()
-            );
-            expect(marketAccount.projectName).to.equal(projectName);
-            expect(marketAccount.isSettled).to.be.false;
-            expect(marketAccount.yesLiquidity.toNumber()).to.equal(0);
-            expect(marketAccount.noLiquidity.toNumber()).to.equal(0);
-            expect(marketAccount.usdcLiquidity.toNumber()).to.equal(0);
-
-            console.log("✅ Market initialized successfully");
+            const market = await createMarket(program, authority, usdcMint, {
+                projectName: "E2E Test Project",
+                tradingCloseTs: new anchor.BN(deadline),
+            });
+            const subsidy = (
+                await program.account.marketState.fetch(market.address)
+            ).usdcLiquidity.toNumber();
 
             // ============================================
-            // STEP 2: Setup User Accounts
+            // STEP 2: User 1 buys YES, user 2 buys NO
             // ============================================
-            console.log("\n=== STEP 2: Setting up user accounts ===");
+            const user1Yes = await buy(program, market, user1, user1Usdc, 0, 50_000_000);
+            const user2No = await buy(program, market, user2, user2Usdc, 1, 20_000_000);
 
-            // Get or create user token accounts
-            const user1YesAta = await getOrCreateAssociatedTokenAccount(
-                provider.connection,
-                user1,
-                yesMint.publicKey,
-                user1.publicKey
-            );
-            const user1NoAta = await getOrCreateAssociatedTokenAccount(
-                provider.connection,
-                user1,
-                noMint.publicKey,
-                user1.publicKey
-            );
-            const user1UsdcAta = await getOrCreateAssociatedTokenAccount(
-                provider.connection,
-                user1,
-                usdcMint.publicKey,
-                user1.publicKey
-            );
-
-            const user2YesAta = await getOrCreateAssociatedTokenAccount(
-                provider.connection,
-                user2,
-                yesMint.publicKey,
-                user2.publicKey
-            );
-            const user2NoAta = await getOrCreateAssociatedTokenAccount(
-                provider.connection,
-                user2,
-                noMint.publicKey,
-                user2.publicKey
-            );
-            const user2UsdcAta = await getOrCreateAssociatedTokenAccount(
-                provider.connection,
-                user2,
-                usdcMint.publicKey,
-                user2.publicKey
-            );
-
-            // Mint USDC to users (10 USDC each = 10,000,000 with 6 decimals)
-            const usdcAmount = 10_000_000; // 10 USDC
-            await mintTo(
-                provider.connection,
-                authority,
-                usdcMint.publicKey,
-                user1UsdcAta.address,
-                authority,
-                usdcAmount
-            );
-            await mintTo(
-                provider.connection,
-                authority,
-                usdcMint.publicKey,
-                user2UsdcAta.address,
-                authority,
-                usdcAmount
-            );
-
-            // Verify USDC balances
-            const user1UsdcBalance = await getAccount(
-                provider.connection,
-                user1UsdcAta.address
-            );
-            const user2UsdcBalance = await getAccount(
-                provider.connection,
-                user2UsdcAta.address
+            const afterBuys = await program.account.marketState.fetch(market.address);
+            expect(afterBuys.usdcLiquidity.toNumber()).to.equal(subsidy + 70_000_000);
+            // The LMSR quantities are re-based, so only their gap tracks the tokens issued
+            expect(
+                (await tokenBalance(provider, user1Yes)) -
+                    (await tokenBalance(provider, user2No))
+            ).to.equal(
+                afterBuys.outcomeQuantities[0].toNumber() -
+                    afterBuys.outcomeQuantities[1].toNumber()
             );
-            expect(Number(user1UsdcBalance.amount)).to.equal(usdcAmount);
-            expect(Number(user2UsdcBalance.amount)).to.equal(usdcAmount);
-
-            console.log("✅ User accounts set up with USDC");
 
             // ============================================
-            // STEP 3: User 1 Buys YES Tokens
+            // STEP 3: User 1 sells part of their YES
             // ============================================
-            console.log("\n=== STEP 3: User 1 buying YES tokens ===");
-
-            const buyAmount1 = new anchor.BN(1_000_000); // 1 USDC
-            const initialYesBalance = Number(
-                (await getAccount(provider.connection, user1YesAta.address))
-                    .amount
-            );
-
-            // Derive liquidity account PDAs
-            const [yesLiquidityAccount] = PublicKey.findProgramAddressSync(
-                [
-                    Buffer.from("liquidity"),
-                    marketPda.toBuffer(),
-                    Buffer.from("yes"),
-                ],
-                program.programId
-            );
-            const [noLiquidityAccount] = PublicKey.findProgramAddressSync(
-                [
-                    Buffer.from("liquidity"),
-                    marketPda.toBuffer(),
-                    Buffer.from("no"),
-                ],
-                program.programId
-            );
-            const [usdcLiquidityAccount] = PublicKey.findProgramAddressSync(
-                [
-                    Buffer.from("liquidity"),
-                    marketPda.toBuffer(),
-                    Buffer.from("usdc"),
-                ],
-                program.programId
-            );
-
-            const buyYesTx = await program.methods
-                .buyTokens(buyAmount1, { yes: {} })
-                .accounts({
-                    market: marketPda,
-                    user: user1.publicKey,
-                    yesMint: yesMint.publicKey,
-                    noMint: noMint.publicKey,
-                    userTokenAccount: user1YesAta.address,
-                    userUsdcAccount: user1UsdcAta.address,
-                    yesLiquidityAccount: yesLiquidityAccount,
-                    noLiquidityAccount: noLiquidityAccount,
-                    usdcLiquidityAccount: usdcLiquidityAccount,
-                    tokenProgram: TOKEN_PROGRAM_ID,
-                } as any)
-                .signers([user1])
-                .rpc();
-
-            console.log("Buy YES transaction:", buyYesTx);
-
-            // Verify market state updated
-            const marketAfterBuy1 = await program.account.marketState.fetch(
-                marketPda
-            );
-            expect(marketAfterBuy1.usdcLiquidity.toNumber()).to.equal(
-                buyAmount1.toNumber()
-            );
-            expect(marketAfterBuy1.yesLiquidity.toNumber()).to.be.greaterThan(
-                0
-            );
-
-            // Verify user received tokens
-            const user1YesBalanceAfter = Number(
-                (await getAccount(provider.connection, user1YesAta.address))
-                    .amount
-            );
-            expect(user1YesBalanceAfter).to.be.greaterThan(initialYesBalance);
-
-            // Verify USDC was deducted
-            const user1UsdcBalanceAfter = await getAccount(
-                provider.connection,
-                user1UsdcAta.address
-            );
-            expect(Number(user1UsdcBalanceAfter.amount)).to.equal(
-                usdcAmount - buyAmount1.toNumber()
+            const yesBeforeSell = await tokenBalance(provider, user1Yes);
+            const usdcBeforeSell = await tokenBalance(provider, user1Usdc);
+            await sell(
+                program,
+                market,
+                user1,
+                user1Usdc,
+                0,
+                Math.floor(yesBeforeSell / 4)
             );
 
-            console.log(
-                `✅ User 1 bought YES tokens. Received: ${
-                    user1YesBalanceAfter / 1e9
-                } tokens`
+            expect(await tokenBalance(provider, user1Yes)).to.be.lessThan(yesBeforeSell);
+            expect(await tokenBalance(provider, user1Usdc)).to.be.greaterThan(
+                usdcBeforeSell
             );
 
             // ============================================
-            // STEP 4: User 2 Buys NO Tokens
+            // STEP 4: Settle after the deadline - goal met, YES wins
             // ============================================
-            console.log("\n=== STEP 4: User 2 buying NO tokens ===");
-
-            const buyAmount2 = new anchor.BN(2_000_000); // 2 USDC
-            const initialNoBalance = Number(
-                (await getAccount(provider.connection, user2NoAta.address))
-                    .amount
-            );
-
-            const buyNoTx = await program.methods
-                .buyTokens(buyAmount2, { no: {} })
-                .accounts({
-                    market: marketPda,
-                    user: user2.publicKey,
-                    yesMint: yesMint.publicKey,
-                    noMint: noMint.publicKey,
-                    userTokenAccount: user2NoAta.address,
-                    userUsdcAccount: user2UsdcAta.address,
-                    yesLiquidityAccount: yesLiquidityAccount,
-                    noLiquidityAccount: noLiquidityAccount,
-                    usdcLiquidityAccount: usdcLiquidityAccount,
-                    tokenProgram: TOKEN_PROGRAM_ID,
-                } as any)
-                .signers([user2])
-                .rpc();
-
-            console.log("Buy NO transaction:", buyNoTx);
-
-            // Verify market state
-            const marketAfterBuy2 = await program.account.marketState.fetch(
-                marketPda
-            );
-            expect(marketAfterBuy2.usdcLiquidity.toNumber()).to.equal(
-                buyAmount1.toNumber() + buyAmount2.toNumber()
-            );
-            expect(marketAfterBuy2.noLiquidity.toNumber()).to.be.greaterThan(0);
-
-            // Verify user received tokens
-            const user2NoBalanceAfter = Number(
-                (await getAccount(provider.connection, user2NoAta.address))
-                    .amount
-            );
-            expect(user2NoBalanceAfter).to.be.greaterThan(initialNoBalance);
+            await waitUntil(provider, deadline);
+            await settle(program, market, 150_000_000_000);
 
-            console.log(
-                `✅ User 2 bought NO tokens. Received: ${
-                    user2NoBalanceAfter / 1e9
-                } tokens`
-            );
+            const settled = await program.account.marketState.fetch(market.address);
+            expect(settled.isSettled).to.be.true;
+            expect(settled.winningOutcome).to.deep.equal(YES);
 
             // ============================================
-            // STEP 5: User 1 Sells Some YES Tokens
+            // STEP 5: Redeem - YES pays 1 USDC per token, NO pays nothing
             // ============================================
-            console.log("\n=== STEP 5: User 1 selling some YES tokens ===");
-
-            const sellAmount = new anchor.BN(user1YesBalanceAfter / 2); // Sell half
-            const user1UsdcBeforeSell = Number(
-                (await getAccount(provider.connection, user1UsdcAta.address))
-                    .amount
-            );
-
-            const sellTx = await program.methods
-                .sellTokens(sellAmount, { yes: {} })
-                .accounts({
-                    market: marketPda,
-                    user: user1.publicKey,
-                    yesMint: yesMint.publicKey,
-                    noMint: noMint.publicKey,
-                    userTokenAccount: user1YesAta.address,
-                    userUsdcAccount: user1UsdcAta.address,
-                    yesLiquidityAccount: yesLiquidityAccount,
-                    noLiquidityAccount: noLiquidityAccount,
-                    usdcLiquidityAccount: usdcLiquidityAccount,
-                    tokenProgram: TOKEN_PROGRAM_ID,
-                } as any)
-                .signers([user1])
-                .rpc();
-
-            console.log("Sell transaction:", sellTx);
-
-            // Verify tokens were burned
-            const user1YesBalanceAfterSell = Number(
-                (await getAccount(provider.connection, user1YesAta.address))
-                    .amount
-            );
-            expect(user1YesBalanceAfterSell).to.be.lessThan(
-                user1YesBalanceAfter
-            );
+            const yesHeld = await tokenBalance(provider, user1Yes);
+            const usdcBeforeRedeem = await tokenBalance(provider, user1Usdc);
+            await redeem(program, market, user1, user1Usdc, 0, yesHeld);
 
-            // Verify USDC was returned
-            const user1UsdcAfterSell = Number(
-                (await getAccount(provider.connection, user1UsdcAta.address))
-                    .amount
+            expect(await tokenBalance(provider, user1Yes)).to.equal(0);
+            expect(await tokenBalance(provider, user1Usdc)).to.equal(
+                usdcBeforeRedeem + yesHeld
             );
-            expect(user1UsdcAfterSell).to.be.greaterThan(user1UsdcBeforeSell);
 
-            console.log(
-                `✅ User 1 sold tokens. USDC returned: ${
-                    (user1UsdcAfterSell - user1UsdcBeforeSell) / 1e6
-                } USDC`
+            await expectError(
+                redeem(program, market, user2, user2Usdc, 1, 1),
+                "WrongTokenType"
             );
-
-            // ============================================
-            // STEP 6: Settlement & Redemption Notes
-            // ============================================
-            console.log("\n=== STEP 6: Settlement & Redemption ===");
-            console.log(
-                "⚠️  Settlement and redemption require deadline to pass"
-            );
-            console.log("   These are tested in separate error handling tests");
-            console.log("   In production, settlement happens after deadline");
-            console.log("   Redemption happens after settlement completes");
-
-            console.log("\n🎉 Complete market lifecycle test passed!");
-            console.log("   ✅ Market initialized");
-            console.log("   ✅ Users bought YES/NO tokens");
-            console.log("   ✅ Users sold tokens back");
-            console.log("   ✅ AMM pricing verified");
-            console.log("   ✅ All core trading flows working!");
         });
     });
 
-    describe("AMM Pricing Verification", () => {
-        it("Should verify AMM pricing formula works correctly", async () => {
-            const fundraisingGoal = new anchor.BN(1000000);
-            const deadline = new anchor.BN(Date.now() / 1000 + 86400 * 30);
-            const testProjectName = "AMM Test Project";
+    describe("LMSR Pricing Verification", () => {
+        it("Should give fewer tokens for the same amount as the price rises", async () => {
+            const market = await createMarket(program, authority, usdcMint);
 
-            // Create new mints for this test market
-            const ammYesMint = Keypair.generate();
-            const ammNoMint = Keypair.generate();
+            const yesAccount = await buy(program, market, user1, user1Usdc, 0, 10_000_000);
+            const firstBuy = await tokenBalance(provider, yesAccount);
+            await buy(program, market, user1, user1Usdc, 0, 10_000_000);
+            const secondBuy = (await tokenBalance(provider, yesAccount)) - firstBuy;
 
-            const yesMintRent = await getMinimumBalanceForRentExemptMint(
-                provider.connection
-            );
-            const createYesMintTx = new anchor.web3.Transaction().add(
-                anchor.web3.SystemProgram.createAccount({
-                    fromPubkey: authority.publicKey,
-                    newAccountPubkey: ammYesMint.publicKey,
-                    space: MINT_SIZE,
-                    lamports: yesMintRent,
-                    programId: TOKEN_PROGRAM_ID,
-                }),
-                createInitializeMintInstruction(
-                    ammYesMint.publicKey,
-                    9,
-                    authority.publicKey,
-                    null
-                )
-            );
-            await provider.sendAndConfirm(createYesMintTx, [
-                authority,
-                ammYesMint,
-            ]);
-
-            const noMintRent = await getMinimumBalanceForRentExemptMint(
-                provider.connection
-            );
-            const createNoMintTx = new anchor.web3.Transaction().add(
-                anchor.web3.SystemProgram.createAccount({
-                    fromPubkey: authority.publicKey,
-                    newAccountPubkey: ammNoMint.publicKey,
-                    space: MINT_SIZE,
-                    lamports: noMintRent,
-                    programId: TOKEN_PROGRAM_ID,
-                }),
-                createInitializeMintInstruction(
-                    ammNoMint.publicKey,
-                    9,
-                    authority.publicKey,
-                    null
-                )
-            );
-            await provider.sendAndConfirm(createNoMintTx, [
-                authority,
-                ammNoMint,
-            ]);
-
-            const [ammMarketPda] = PublicKey.findProgramAddressSync(
-                [
-                    Buffer.from("market_v2"),
-                    authority.publicKey.toBuffer(),
-                    Buffer.from(testProjectName),
-                ],
-                program.programId
-            );
-
-            // Transfer mint authority to Market PDA
-            const transferYesAuthTx = new anchor.web3.Transaction().add(
-                createSetAuthorityInstruction(
-                    ammYesMint.publicKey,
-                    authority.publicKey,
-                    AuthorityType.MintTokens,
-                    ammMarketPda
-                )
-            );
-            await provider.sendAndConfirm(transferYesAuthTx, [authority]);
-
-            const transferNoAuthTx = new anchor.web3.Transaction().add(
-                createSetAuthorityInstruction(
-                    ammNoMint.publicKey,
-                    authority.publicKey,
-                    AuthorityType.MintTokens,
-                    ammMarketPda
-                )
-            );
-            await provider.sendAndConfirm(transferNoAuthTx, [authority]);
-
-            // Initialize market
-            await program.methods
-                .initialize(fundraisingGoal, deadline, testProjectName)
-                .accounts({
-                    market: ammMarketPda,
-                    authority: authority.publicKey,
-                    yesMint: ammYesMint.publicKey,
-                    noMint: ammNoMint.publicKey,
-                    usdcMint: usdcMint.publicKey,
-                    tokenProgram: TOKEN_PROGRAM_ID,
-                    systemProgram: SystemProgram.programId,
-                    rent: SYSVAR_RENT_PUBKEY,
-                } as any)
-                .signers([authority])
-                .rpc();
-
-            // Setup user
-            const ammUser = Keypair.generate();
-            await provider.connection.requestAirdrop(
-                ammUser.publicKey,
-                2 * LAMPORTS_PER_SOL
-            );
-            await new Promise((resolve) => setTimeout(resolve, 1000));
-
-            const ammUserYesAta = await getOrCreateAssociatedTokenAccount(
-                provider.connection,
-                ammUser,
-                ammYesMint.publicKey,
-                ammUser.publicKey
-            );
-            const ammUserUsdcAta = await getOrCreateAssociatedTokenAccount(
-                provider.connection,
-                ammUser,
-                usdcMint.publicKey,
-                ammUser.publicKey
-            );
+            expect(secondBuy).to.be.greaterThan(0);
+            expect(secondBuy).to.be.lessThan(firstBuy);
+        });
+    });
 
-            // Mint USDC
-            await mintTo(
-                provider.connection,
-                authority,
-                usdcMint.publicKey,
-                ammUserUsdcAta.address,
-                authority,
-                10_000_000 // 10 USDC
-            );
+    describe("Parimutuel Lifecycle", () => {
+        it("Should split the pot between winning stakes", async () => {
+            const now = await chainTime(provider);
+            const deadline = now + 6;
+            const market = await createMarket(program, authority, usdcMint, {
+                mode: parimutuelMode,
+                tradingCloseTs: new anchor.BN(deadline),
+            });
+
+            // Stakes mint one token per USDC
+            const user1Yes = await buy(program, market, user1, user1Usdc, 0, 30_000_000);
+            await buy(program, market, user2, user2Usdc, 1, 10_000_000);
+            expect(await tokenBalance(provider, user1Yes)).to.equal(30_000_000);
+
+            await expectError(
+                sell(program, market, user1, user1Usdc, 0, 1_000_000),
+                "SellingDisabled"
+            );
+
+            await waitUntil(provider, deadline);
+            await settle(program, market, 150_000_000_000);
+
+            // The only YES stake takes the whole 40 USDC pot
+            const usdcBefore = await tokenBalance(provider, user1Usdc);
+            await redeem(program, market, user1, user1Usdc, 0, 30_000_000);
+            const received = (await tokenBalance(provider, user1Usdc)) - usdcBefore;
+            expect(received).to.be.at.most(40_000_000);
+            expect(received).to.be.at.least(39_999_990);
+        });
 
-            // First buy: 1 USDC (should get 1:1 ratio since no liquidity)
-            const buy1 = new anchor.BN(1_000_000);
-            const [ammYesLiquidity] = PublicKey.findProgramAddressSync(
-                [
-                    Buffer.from("liquidity"),
-                    ammMarketPda.toBuffer(),
-                    Buffer.from("yes"),
-                ],
-                program.programId
-            );
-            const [ammNoLiquidity] = PublicKey.findProgramAddressSync(
-                [
-                    Buffer.from("liquidity"),
-                    ammMarketPda.toBuffer(),
-                    Buffer.from("no"),
-                ],
-                program.programId
-            );
-            const [ammUsdcLiquidity] = PublicKey.findProgramAddressSync(
-                [
-                    Buffer.from("liquidity"),
-                    ammMarketPda.toBuffer(),
-                    Buffer.from("usdc"),
-                ],
-                program.programId
-            );
+        it("Should refund every stake when nobody backed the winner", async () => {
+            const now = await chainTime(provider);
+            const tradingCloseTs = now + 6;
+            const market = await createMarket(program, authority, usdcMint, {
+                mode: parimutuelMode,
+                feeBps: 500,
+                tradingCloseTs: new anchor.BN(tradingCloseTs),
+            });
 
-            await program.methods
-                .buyTokens(buy1, { yes: {} })
-                .accounts({
-                    market: ammMarketPda,
-                    user: ammUser.publicKey,
-                    yesMint: ammYesMint.publicKey,
-                    noMint: ammNoMint.publicKey,
-                    userTokenAccount: ammUserYesAta.address,
-                    userUsdcAccount: ammUserUsdcAta.address,
-                    yesLiquidityAccount: ammYesLiquidity,
-                    noLiquidityAccount: ammNoLiquidity,
-                    usdcLiquidityAccount: ammUsdcLiquidity,
-                    tokenProgram: TOKEN_PROGRAM_ID,
-                } as any)
-                .signers([ammUser])
-                .rpc();
-
-            const marketAfterBuy1 = await program.account.marketState.fetch(
-                ammMarketPda
-            );
-            const tokensReceived1 = Number(
-                (await getAccount(provider.connection, ammUserYesAta.address))
-                    .amount
-            );
+            await buy(program, market, user2, user2Usdc, 1, 10_000_000);
 
-            // Verify 1:1 ratio for first buy
-            expect(tokensReceived1).to.equal(buy1.toNumber());
-            expect(marketAfterBuy1.usdcLiquidity.toNumber()).to.equal(
-                buy1.toNumber()
-            );
-            expect(marketAfterBuy1.yesLiquidity.toNumber()).to.equal(
-                buy1.toNumber()
-            );
+            await waitUntil(provider, tradingCloseTs);
+            await settle(program, market, 150_000_000_000);
 
-            // Second buy: 1 more USDC (should get less tokens due to AMM)
-            await program.methods
-                .buyTokens(buy1, { yes: {} })
-                .accounts({
-                    market: ammMarketPda,
-                    user: ammUser.publicKey,
-                    yesMint: ammYesMint.publicKey,
-                    noMint: ammNoMint.publicKey,
-                    userTokenAccount: ammUserYesAta.address,
-                    userUsdcAccount: ammUserUsdcAta.address,
-                    yesLiquidityAccount: ammYesLiquidity,
-                    noLiquidityAccount: ammNoLiquidity,
-                    usdcLiquidityAccount: ammUsdcLiquidity,
-                    tokenProgram: TOKEN_PROGRAM_ID,
-                } as any)
-                .signers([ammUser])
-                .rpc();
-
-            const tokensReceived2 =
-                Number(
-                    (
-                        await getAccount(
-                            provider.connection,
-                            ammUserYesAta.address
-                        )
-                    ).amount
-                ) - tokensReceived1;
-
-            // Second buy should receive less tokens (AMM price impact)
-            expect(tokensReceived2).to.be.lessThan(tokensReceived1);
-            expect(tokensReceived2).to.be.greaterThan(0);
-
-            console.log(
-                `First buy: ${tokensReceived1 / 1e9} tokens for ${
-                    buy1.toNumber() / 1e6
-                } USDC`
-            );
-            console.log(
-                `Second buy: ${tokensReceived2 / 1e9} tokens for ${
-                    buy1.toNumber() / 1e6
-                } USDC`
-            );
-            console.log(
-                "✅ AMM pricing verified - second buy received fewer tokens"
+            // YES won with no stakes, so the NO stake gets its money back, fee-free
+            const settled = await program.account.marketState.fetch(market.address);
+            expect(settled.accruedFees.toNumber()).to.equal(0);
+            const usdcBefore = await tokenBalance(provider, user2Usdc);
+            await redeem(program, market, user2, user2Usdc, 1, 10_000_000);
+            expect(await tokenBalance(provider, user2Usdc)).to.equal(
+                usdcBefore + 10_000_000
             );
         });
     });
 
     describe("Error Handling", () => {
-        it("Should reject trades after deadline", async () => {
-            const pastDeadline = new anchor.BN(Date.now() / 1000 - 86400);
-            const testProjectName = "Deadline Test";
-            const [deadlineMarketPda] = PublicKey.findProgramAddressSync(
-                [
-                    Buffer.from("market_v2"),
-                    authority.publicKey.toBuffer(),
-                    Buffer.from(testProjectName),
-                ],
-                program.programId
-            );
+        it("Should reject trades after trading closes", async () => {
+            const now = await chainTime(provider);
+            const market = await createMarket(program, authority, usdcMint, {
+                tradingCloseTs: new anchor.BN(now + 3),
+            });
 
-            // This will fail because deadline is in the past
-            try {
-                await program.methods
-                    .initialize(
-                        new anchor.BN(1000000),
-                        pastDeadline,
-                        testProjectName
-                    )
-                    .accounts({
-                        market: deadlineMarketPda,
-                        authority: authority.publicKey,
-                        yesMint: yesMint.publicKey,
-                        noMint: noMint.publicKey,
-                        usdcMint: usdcMint.publicKey,
-                        tokenProgram: TOKEN_PROGRAM_ID,
-                        systemProgram: SystemProgram.programId,
-                        rent: SYSVAR_RENT_PUBKEY,
-                    } as any)
-                    .signers([authority])
-                    .rpc();
-
-                expect.fail("Should have thrown InvalidDeadline error");
-            } catch (err: any) {
-                expect(err.toString()).to.include("InvalidDeadline");
-            }
+            await waitUntil(provider, now + 3);
+            await expectError(
+                buy(program, market, user1, user1Usdc, 0, 1_000_000),
+                "DeadlinePassed"
+            );
         });
 
-        it("Should reject settlement before deadline", async () => {
-            try {
-                await program.methods
-                    .settleMarket(new anchor.BN(1000000))
-                    .accounts({
-                        market: marketPda,
-                        authority: authority.publicKey,
-                    } as any)
-                    .signers([authority])
-                    .rpc();
-
-                expect.fail("Should have thrown DeadlineNotPassed error");
-            } catch (err: any) {
-                expect(err.toString()).to.include("DeadlineNotPassed");
-            }
+        it("Should reject settlement by anyone but the authority", async () => {
+            const market = await createMarket(program, authority, usdcMint);
+
+            await expectError(
+                settle(program, { ...market, authority: user1 }, 150_000_000_000),
+                "Unauthorized"
+            );
         });
 
         it("Should reject trading after settlement", async () => {
-            // Create and settle a market
-            const settleTestName = "Settled Trade Test";
-            const futureDeadline = new anchor.BN(Date.now() / 1000 + 86400);
-            const [settledMarketPda] = PublicKey.findProgramAddressSync(
-                [
-                    Buffer.from("market_v2"),
-                    authority.publicKey.toBuffer(),
-                    Buffer.from(settleTestName),
-                ],
-                program.programId
-            );
+            const now = await chainTime(provider);
+            const market = await createMarket(program, authority, usdcMint, {
+                tradingCloseTs: new anchor.BN(now + 3),
+            });
 
-            // This test requires a market that's already settled
-            // We'll skip the actual trade test since we can't easily advance time
-            // But we verify the error handling structure is in place
-            console.log("✅ Error handling tests verify proper validation");
+            await waitUntil(provider, now + 3);
+            await settle(program, market, 0);
+
+            await expectError(
+                buy(program, market, user1, user1Usdc, 0, 1_000_000),
+                "MarketSettled"
+            );
         });
     });
 });
-
-
-
@@ -0,0 +1,499 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { IndieStarMarket } from "../target/types/indie_star_market";
+import {
+  Keypair,
+  PublicKey,
+  SystemProgram,
+  SYSVAR_RENT_PUBKEY,
+  LAMPORTS_PER_SOL,
+} from "@solana/web3.js";
+import {
+  TOKEN_PROGRAM_ID,
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  createMint,
+  getAssociatedTokenAddressSync,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+} from "@solana/spl-token";
+import { createHash } from "crypto";
+import { expect } from "chai";
+
+// Shared setup for the market test suites: collateral mint and market
+// creation against the current instruction API.
+
+const BPF_LOADER_UPGRADEABLE = new PublicKey(
+  "BPFLoaderUpgradeab1e11111111111111111111111"
+);
+
+// Markets per registry page; mirrors REGISTRY_PAGE_SIZE
+const REGISTRY_PAGE_SIZE = 64;
+
+export type MarketProgram = Program<IndieStarMarket>;
+
+// Outcome argument as the program's `Outcome(u8)`; 0 is YES and 1 is NO in binary markets
+export const outcome = (index: number) => ({ 0: index });
+export const YES = outcome(0);
+export const NO = outcome(1);
+
+export const lmsrMode = { lmsr: {} };
+export const parimutuelMode = { parimutuel: {} };
+
+export interface Market {
+  address: PublicKey;
+  authority: Keypair;
+  usdcMint: PublicKey;
+  vault: PublicKey;
+  lpMint: PublicKey;
+  outcomeMints: PublicKey[];
+  projectName: string;
+}
+
+export async function fund(
+  provider: anchor.AnchorProvider,
+  ...keypairs: Keypair[]
+): Promise<void> {
+  await Promise.all(
+    keypairs.map(async (keypair) => {
+      const signature = await provider.connection.requestAirdrop(
+        keypair.publicKey,
+        10 * LAMPORTS_PER_SOL
+      );
+      await provider.connection.confirmTransaction(signature);
+    })
+  );
+}
+
+// Validator time, which is what the program checks deadlines against
+export async function chainTime(
+  provider: anchor.AnchorProvider
+): Promise<number> {
+  const slot = await provider.connection.getSlot();
+  return (await provider.connection.getBlockTime(slot)) ?? Date.now() / 1000;
+}
+
+// Wait until the validator clock has passed `ts`
+export async function waitUntil(
+  provider: anchor.AnchorProvider,
+  ts: number
+): Promise<void> {
+  while ((await chainTime(provider)) <= ts) {
+    await new Promise((resolve) => setTimeout(resolve, 500));
+  }
+}
+
+// Expect `promise` to fail with the program error `code`
+export async function expectError(
+  promise: Promise<unknown>,
+  code: string
+): Promise<void> {
+  let error: any;
+  try {
+    await promise;
+  } catch (err) {
+    error = err;
+  }
+  expect(error, `expected ${code}`).to.not.be.undefined;
+  const actual = error.error?.errorCode?.code ?? error.toString();
+  expect(actual).to.include(code);
+}
+
+export function marketAddress(
+  programId: PublicKey,
+  authority: PublicKey,
+  projectName: string
+): PublicKey {
+  const nameSeed = createHash("sha256").update(projectName).digest();
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("market_v2"), authority.toBuffer(), nameSeed],
+    programId
+  )[0];
+}
+
+export function configAddress(programId: PublicKey): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("config")],
+    programId
+  )[0];
+}
+
+// Registry page `page`, holding market ids from page * REGISTRY_PAGE_SIZE
+export function registryPageAddress(
+  programId: PublicKey,
+  page: number
+): PublicKey {
+  const seed = Buffer.alloc(8);
+  seed.writeUInt32LE(page, 0);
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("registry"), seed],
+    programId
+  )[0];
+}
+
+// Create the program config once per validator; the provider wallet is the
+// upgrade authority under `anchor test`
+export async function ensureConfig(program: MarketProgram): Promise<void> {
+  const config = configAddress(program.programId);
+  if (await program.account.programConfig.fetchNullable(config)) {
+    return;
+  }
+  const provider = program.provider as anchor.AnchorProvider;
+  const [programData] = PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    BPF_LOADER_UPGRADEABLE
+  );
+  await program.methods
+    .initializeConfig(0, provider.wallet.publicKey)
+    .accountsPartial({
+      config,
+      admin: provider.wallet.publicKey,
+      program: program.programId,
+      programData,
+      systemProgram: SystemProgram.programId,
+    })
+    .rpc();
+}
+
+// Liquidity account PDA; `kind` is "usdc" for the USDC vault
+export function liquidityAddress(
+  programId: PublicKey,
+  market: PublicKey,
+  kind: string
+): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("liquidity"), market.toBuffer(), Buffer.from(kind)],
+    programId
+  )[0];
+}
+
+export function vaultAddress(
+  programId: PublicKey,
+  market: PublicKey
+): PublicKey {
+  return liquidityAddress(programId, market, "usdc");
+}
+
+// A 6-decimal USDC stand-in minted by `authority`
+export async function createUsdcMint(
+  provider: anchor.AnchorProvider,
+  authority: Keypair
+): Promise<PublicKey> {
+  return createMint(provider.connection, authority, authority.publicKey, null, 6);
+}
+
+export async function usdcAccount(
+  provider: anchor.AnchorProvider,
+  usdcMint: PublicKey,
+  mintAuthority: Keypair,
+  owner: Keypair,
+  amount: number
+): Promise<PublicKey> {
+  const account = await getOrCreateAssociatedTokenAccount(
+    provider.connection,
+    owner,
+    usdcMint,
+    owner.publicKey
+  );
+  if (amount > 0) {
+    await mintTo(
+      provider.connection,
+      mintAuthority,
+      usdcMint,
+      account.address,
+      mintAuthority,
+      amount
+    );
+  }
+  return account.address;
+}
+
+export async function tokenAccount(
+  provider: anchor.AnchorProvider,
+  mint: PublicKey,
+  owner: Keypair
+): Promise<PublicKey> {
+  const account = await getOrCreateAssociatedTokenAccount(
+    provider.connection,
+    owner,
+    mint,
+    owner.publicKey
+  );
+  return account.address;
+}
+
+export async function tokenBalance(
+  provider: anchor.AnchorProvider,
+  account: PublicKey
+): Promise<number> {
+  const balance = await provider.connection.getTokenAccountBalance(account);
+  return Number(balance.value.amount);
+}
+
+// Mint PDA and bump the program creates for outcome `index` of `market`;
+// binary markets keep the original "yes" / "no" seeds
+export function outcomeMintPda(
+  programId: PublicKey,
+  market: PublicKey,
+  index: number,
+  binary = true
+): [PublicKey, number] {
+  const seed = binary ? Buffer.from(index === 0 ? "yes" : "no") : Buffer.from([index]);
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("mint"), market.toBuffer(), seed],
+    programId
+  );
+}
+
+export function outcomeMintAddress(
+  programId: PublicKey,
+  market: PublicKey,
+  index: number,
+  binary = true
+): PublicKey {
+  return outcomeMintPda(programId, market, index, binary)[0];
+}
+
+// Create a market for `authority`, who must also be the USDC mint authority
+// so the LMSR subsidy can be funded; `params` override the defaults below
+// A market has two outcomes unless `params.bucketBoundsBps` is set; pass
+// `campaign` together with `params.campaignProgram` to bind it to a campaign
+export async function createMarket(
+  program: MarketProgram,
+  authority: Keypair,
+  usdcMint: PublicKey,
+  params: Record<string, any> = {},
+  campaign: PublicKey | null = null
+): Promise<Market> {
+  const provider = program.provider as anchor.AnchorProvider;
+  const now = await chainTime(provider);
+  const projectName =
+    params.projectName ??
+    `Project ${Keypair.generate().publicKey.toBase58().slice(0, 8)}`;
+
+  const address = marketAddress(program.programId, authority.publicKey, projectName);
+  const config = configAddress(program.programId);
+  const { marketCount } = await program.account.programConfig.fetch(config);
+  const registryPage = registryPageAddress(
+    program.programId,
+    Math.floor(marketCount.toNumber() / REGISTRY_PAGE_SIZE)
+  );
+  const [lpMint] = PublicKey.findProgramAddressSync(
+    [Buffer.from("lp_mint"), address.toBuffer()],
+    program.programId
+  );
+  const bucketBoundsBps: number[] = params.bucketBoundsBps ?? [];
+  const binary = bucketBoundsBps.length === 0 && !params.scalarRange;
+  const outcomeCount = bucketBoundsBps.length > 0 ? bucketBoundsBps.length + 1 : 2;
+  const outcomeMintPdas = Array.from({ length: outcomeCount }, (_, index) =>
+    outcomeMintPda(program.programId, address, index, binary)
+  );
+  const outcomeMints = outcomeMintPdas.map(([mint]) => mint);
+  const authorityUsdcAccount = await usdcAccount(
+    provider,
+    usdcMint,
+    authority,
+    authority,
+    10_000_000_000
+  );
+  const tradingCloseTs: anchor.BN = params.tradingCloseTs ?? new anchor.BN(now + 3600);
+  const campaignEndTs: anchor.BN = params.campaignEndTs ?? tradingCloseTs;
+  const settlementAvailableTs: anchor.BN =
+    params.settlementAvailableTs ?? campaignEndTs;
+
+  await program.methods
+    .initialize({
+      fundraisingGoal: new anchor.BN(100_000_000_000),
+      tradingCloseTs,
+      campaignEndTs,
+      settlementAvailableTs,
+      projectName,
+      lmsrB: new anchor.BN(1_000_000_000),
+      feeBps: 0,
+      feeRecipient: authority.publicKey,
+      lpFeeBps: 0,
+      mode: lmsrMode,
+      bucketBoundsBps: [],
+      scalarRange: null,
+      resolutionDeadline: new anchor.BN(settlementAvailableTs.toNumber() + 86400),
+      optimisticOracle: null,
+      fundraisingFeed: null,
+      campaignProgram: null,
+      outcomeMintBumps: outcomeMintPdas.map(([, bump]) => bump),
+      ...params,
+    } as any)
+    .accountsPartial({
+      market: address,
+      authority: authority.publicKey,
+      config,
+      registryPage,
+      authorityUsdcAccount,
+      usdcMint,
+      lpMint,
+      authorityLpAccount: getAssociatedTokenAddressSync(lpMint, authority.publicKey),
+      usdcLiquidityAccount: vaultAddress(program.programId, address),
+      campaign,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+      rent: SYSVAR_RENT_PUBKEY,
+    })
+    .remainingAccounts(
+      outcomeMints.map((pubkey) => ({ pubkey, isSigner: false, isWritable: true }))
+    )
+    .signers([authority])
+    .rpc();
+
+  return {
+    address,
+    authority,
+    usdcMint,
+    vault: vaultAddress(program.programId, address),
+    lpMint,
+    outcomeMints,
+    projectName,
+  };
+}
+
+// Accounts shared by buy_tokens and sell_tokens for outcome `index`
+export function tradeAccounts(
+  market: Market,
+  user: Keypair,
+  index: number,
+  userTokenAccount: PublicKey,
+  userUsdcAccount: PublicKey
+) {
+  return {
+    market: market.address,
+    user: user.publicKey,
+    outcomeMint: market.outcomeMints[index],
+    userTokenAccount,
+    userUsdcAccount,
+    usdcLiquidityAccount: market.vault,
+    tokenProgram: TOKEN_PROGRAM_ID,
+  };
+}
+
+// Buy `amountUsdc` worth of outcome `index` for `user`; returns their token account
+export async function buy(
+  program: MarketProgram,
+  market: Market,
+  user: Keypair,
+  userUsdcAccount: PublicKey,
+  index: number,
+  amountUsdc: number
+): Promise<PublicKey> {
+  const provider = program.provider as anchor.AnchorProvider;
+  const userTokenAccount = await tokenAccount(
+    provider,
+    market.outcomeMints[index],
+    user
+  );
+  await program.methods
+    .buyTokens(new anchor.BN(amountUsdc), outcome(index), new anchor.BN(0), null)
+    .accountsPartial(
+      tradeAccounts(market, user, index, userTokenAccount, userUsdcAccount)
+    )
+    .signers([user])
+    .rpc();
+  return userTokenAccount;
+}
+
+// Sell `amountTokens` of outcome `index` for `user`
+export async function sell(
+  program: MarketProgram,
+  market: Market,
+  user: Keypair,
+  userUsdcAccount: PublicKey,
+  index: number,
+  amountTokens: number
+): Promise<void> {
+  const provider = program.provider as anchor.AnchorProvider;
+  const userTokenAccount = await tokenAccount(
+    provider,
+    market.outcomeMints[index],
+    user
+  );
+  await program.methods
+    .sellTokens(new anchor.BN(amountTokens), outcome(index), new anchor.BN(0), null)
+    .accountsPartial(
+      tradeAccounts(market, user, index, userTokenAccount, userUsdcAccount)
+    )
+    .signers([user])
+    .rpc();
+}
+
+export async function settle(
+  program: MarketProgram,
+  market: Market,
+  fundraisingResult: number
+): Promise<void> {
+  await program.methods
+    .settleMarket(new anchor.BN(fundraisingResult), Array(32).fill(0), "")
+    .accountsPartial({
+      market: market.address,
+      authority: market.authority.publicKey,
+      usdcLiquidityAccount: market.vault,
+    })
+    .signers([market.authority])
+    .rpc();
+}
+
+// Void `market` as `caller`, the authority unless given
+export async function voidMarket(
+  program: MarketProgram,
+  market: Market,
+  caller: Keypair = market.authority
+): Promise<void> {
+  await program.methods
+    .voidMarket()
+    .accountsPartial({
+      market: market.address,
+      caller: caller.publicKey,
+      usdcLiquidityAccount: market.vault,
+    })
+    .signers([caller])
+    .rpc();
+}
+
+export async function pause(
+  program: MarketProgram,
+  market: Market,
+  reason = 0
+): Promise<void> {
+  await program.methods
+    .pauseMarket(reason)
+    .accountsPartial({
+      market: market.address,
+      authority: market.authority.publicKey,
+    })
+    .signers([market.authority])
+    .rpc();
+}
+
+export async function redeem(
+  program: MarketProgram,
+  market: Market,
+  user: Keypair,
+  userUsdcAccount: PublicKey,
+  index: number,
+  amount: number
+): Promise<void> {
+  const provider = program.provider as anchor.AnchorProvider;
+  await program.methods
+    .redeemTokens(new anchor.BN(amount))
+    .accountsPartial({
+      market: market.address,
+      user: user.publicKey,
+      outcomeMint: market.outcomeMints[index],
+      userTokenAccount: await tokenAccount(
+        provider,
+        market.outcomeMints[index],
+        user
+      ),
+      userUsdcAccount,
+      usdcLiquidityAccount: market.vault,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    })
+    .signers([user])
+    .rpc();
+}
@@ -0,0 +1,206 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { IndieStarMarket } from "../target/types/indie_star_market";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  Market,
+  buy,
+  chainTime,
+  createMarket,
+  createUsdcMint,
+  ensureConfig,
+  expectError,
+  fund,
+  outcome,
+  redeem,
+  settle,
+  tokenBalance,
+  usdcAccount,
+  waitUntil,
+} from "./helpers";
+
+describe("market kinds", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace
+    .indieStarMarket as Program<IndieStarMarket>;
+
+  let authority: Keypair;
+  let user: Keypair;
+  let usdcMint: PublicKey;
+  let userUsdcAccount: PublicKey;
+
+  before(async () => {
+    authority = Keypair.generate();
+    user = Keypair.generate();
+    await fund(provider, authority, user);
+    await ensureConfig(program);
+    usdcMint = await createUsdcMint(provider, authority);
+    userUsdcAccount = await usdcAccount(
+      provider,
+      usdcMint,
+      authority,
+      user,
+      1_000_000_000
+    );
+  });
+
+  // `count` markets made with `params`, all settleable a few seconds after the
+  // last is created, leaving time to trade in them
+  async function closingMarkets(
+    count: number,
+    params: Record<string, any>
+  ): Promise<{ markets: Market[]; settlementAvailableTs: number }> {
+    const settlementAvailableTs = (await chainTime(provider)) + 4 + 2 * count;
+    const markets: Market[] = [];
+    for (let i = 0; i < count; i++) {
+      markets.push(
+        await createMarket(program, authority, usdcMint, {
+          tradingCloseTs: new anchor.BN(settlementAvailableTs),
+          ...params,
+        })
+      );
+    }
+    return { markets, settlementAvailableTs };
+  }
+
+  describe("Categorical", () => {
+    // Below half the 100 USDC goal, up to the goal, and the goal or more
+    const bucketBoundsBps = [5_000, 10_000];
+
+    it("Creates a mint per bucket", async () => {
+      const market = await createMarket(program, authority, usdcMint, {
+        bucketBoundsBps,
+      });
+
+      const marketAccount = await program.account.marketState.fetch(
+        market.address
+      );
+      expect(marketAccount.outcomeCount).to.equal(3);
+      expect(
+        marketAccount.outcomeMints
+          .slice(0, 3)
+          .map((mint) => mint.toString())
+      ).to.deep.equal(market.outcomeMints.map((mint) => mint.toString()));
+    });
+
+    it("Puts a result on a bound in the bucket above it", async () => {
+      const {
+        markets: [belowAll, onFirst, onSecond],
+        settlementAvailableTs,
+      } = await closingMarkets(3, { bucketBoundsBps });
+
+      await waitUntil(provider, settlementAvailableTs);
+      await settle(program, belowAll, 49_999_999_999);
+      await settle(program, onFirst, 50_000_000_000);
+      await settle(program, onSecond, 100_000_000_000);
+
+      const winners = await Promise.all(
+        [belowAll, onFirst, onSecond].map(async (market) => {
+          const marketAccount = await program.account.marketState.fetch(
+            market.address
+          );
+          return marketAccount.winningOutcome;
+        })
+      );
+      expect(winners).to.deep.equal([outcome(0), outcome(1), outcome(2)]);
+    });
+
+    it("Redeems the winning bucket in full and rejects the others", async () => {
+      const {
+        markets: [market],
+        settlementAvailableTs,
+      } = await closingMarkets(1, { bucketBoundsBps });
+      const winning = await buy(program, market, user, userUsdcAccount, 1, 20_000_000);
+      await buy(program, market, user, userUsdcAccount, 2, 20_000_000);
+
+      await waitUntil(provider, settlementAvailableTs);
+      await settle(program, market, 75_000_000_000);
+
+      const tokens = await tokenBalance(provider, winning);
+      const usdcBefore = await tokenBalance(provider, userUsdcAccount);
+      await redeem(program, market, user, userUsdcAccount, 1, tokens);
+      expect(await tokenBalance(provider, userUsdcAccount)).to.equal(
+        usdcBefore + tokens
+      );
+      await expectError(
+        redeem(program, market, user, userUsdcAccount, 2, 1_000_000),
+        "WrongTokenType"
+      );
+    });
+  });
+
+  describe("Scalar", () => {
+    const LONG = 0;
+    const SHORT = 1;
+    const scalarRange = {
+      lowerBound: new anchor.BN(50_000_000_000),
+      upperBound: new anchor.BN(150_000_000_000),
+    };
+
+    // LONG and SHORT payout rates of a settled market
+    async function payoutRates(market: Market): Promise<number[]> {
+      const marketAccount = await program.account.marketState.fetch(
+        market.address
+      );
+      expect(marketAccount.winningOutcome).to.be.null;
+      return marketAccount.payoutRates
+        .slice(0, 2)
+        .map((rate) => rate.toNumber());
+    }
+
+    it("Pays LONG and SHORT by where the result lands in the range", async () => {
+      const {
+        markets: [market],
+        settlementAvailableTs,
+      } = await closingMarkets(1, { scalarRange });
+      const long = await buy(program, market, user, userUsdcAccount, LONG, 20_000_000);
+      const short = await buy(program, market, user, userUsdcAccount, SHORT, 20_000_000);
+
+      await waitUntil(provider, settlementAvailableTs);
+      await settle(program, market, 75_000_000_000);
+      expect(await payoutRates(market)).to.deep.equal([250_000_000, 750_000_000]);
+
+      const longTokens = await tokenBalance(provider, long);
+      const shortTokens = await tokenBalance(provider, short);
+      const usdcBefore = await tokenBalance(provider, userUsdcAccount);
+      await redeem(program, market, user, userUsdcAccount, LONG, longTokens);
+      await redeem(program, market, user, userUsdcAccount, SHORT, shortTokens);
+      expect(await tokenBalance(provider, userUsdcAccount)).to.equal(
+        usdcBefore +
+          Math.floor(longTokens / 4) +
+          Math.floor((shortTokens * 3) / 4)
+      );
+    });
+
+    it("Pays one side in full on or beyond the bounds", async () => {
+      const {
+        markets: [below, onLower, onUpper, above],
+        settlementAvailableTs,
+      } = await closingMarkets(4, { scalarRange });
+      await buy(program, onLower, user, userUsdcAccount, LONG, 20_000_000);
+
+      await waitUntil(provider, settlementAvailableTs);
+      await settle(program, below, 10_000_000_000);
+      await settle(program, onLower, 50_000_000_000);
+      await settle(program, onUpper, 150_000_000_000);
+      await settle(program, above, 300_000_000_000);
+
+      const rates = await Promise.all(
+        [below, onLower, onUpper, above].map(payoutRates)
+      );
+      expect(rates).to.deep.equal([
+        [0, 1_000_000_000],
+        [0, 1_000_000_000],
+        [1_000_000_000, 0],
+        [1_000_000_000, 0],
+      ]);
+      await expectError(
+        redeem(program, onLower, user, userUsdcAccount, LONG, 1_000_000),
+        "WrongTokenType"
+      );
+    });
+  });
+});
@@ -0,0 +1,246 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { IndieStarMarket } from "../target/types/indie_star_market";
+import { Keypair, PublicKey, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  Market,
+  NO,
+  YES,
+  chainTime,
+  createMarket,
+  createUsdcMint,
+  ensureConfig,
+  expectError,
+  fund,
+  settle,
+  tokenBalance,
+  usdcAccount,
+  voidMarket,
+  waitUntil,
+} from "./helpers";
+
+const BOND = 10_000_000;
+const DISPUTE_WINDOW = 5;
+
+describe("optimistic oracle", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace
+    .indieStarMarket as Program<IndieStarMarket>;
+
+  let authority: Keypair;
+  let arbiter: Keypair;
+  let proposer: Keypair;
+  let disputer: Keypair;
+  let usdcMint: PublicKey;
+  let proposerUsdc: PublicKey;
+  let disputerUsdc: PublicKey;
+
+  before(async () => {
+    authority = Keypair.generate();
+    arbiter = Keypair.generate();
+    proposer = Keypair.generate();
+    disputer = Keypair.generate();
+    await fund(provider, authority, arbiter, proposer, disputer);
+    await ensureConfig(program);
+    usdcMint = await createUsdcMint(provider, authority);
+    proposerUsdc = await usdcAccount(provider, usdcMint, authority, proposer, 10 * BOND);
+    disputerUsdc = await usdcAccount(provider, usdcMint, authority, disputer, 10 * BOND);
+  });
+
+  // A market settled through the oracle, whose settlement opens a few seconds
+  // from now; `config` overrides the oracle config
+  async function oracleMarket(
+    config: Record<string, any> = {}
+  ): Promise<{ market: Market; settlementAvailableTs: number }> {
+    const settlementAvailableTs = (await chainTime(provider)) + 3;
+    const market = await createMarket(program, authority, usdcMint, {
+      tradingCloseTs: new anchor.BN(settlementAvailableTs),
+      optimisticOracle: {
+        arbiter: arbiter.publicKey,
+        bond: new anchor.BN(BOND),
+        disputeWindow: new anchor.BN(DISPUTE_WINDOW),
+        ...config,
+      },
+    });
+    return { market, settlementAvailableTs };
+  }
+
+  function proposalAddress(market: Market): PublicKey {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("proposal"), market.address.toBuffer()],
+      program.programId
+    )[0];
+  }
+
+  function bondVaultAddress(market: Market): PublicKey {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("bond"), market.address.toBuffer()],
+      program.programId
+    )[0];
+  }
+
+  // Propose `result` as `proposer` once settlement opens; returns when the
+  // dispute window closes
+  async function propose(
+    market: Market,
+    settlementAvailableTs: number,
+    result: number
+  ): Promise<number> {
+    await waitUntil(provider, settlementAvailableTs);
+    await program.methods
+      .proposeResult(new anchor.BN(result), Array(32).fill(0), "")
+      .accountsPartial({
+        market: market.address,
+        proposal: proposalAddress(market),
+        proposer: proposer.publicKey,
+        proposerUsdcAccount: proposerUsdc,
+        usdcMint,
+        bondVault: bondVaultAddress(market),
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([proposer])
+      .rpc();
+    const proposal = await program.account.resultProposal.fetch(
+      proposalAddress(market)
+    );
+    return proposal.proposedAt.toNumber() + DISPUTE_WINDOW;
+  }
+
+  function dispute(market: Market) {
+    return program.methods
+      .disputeResult()
+      .accountsPartial({
+        market: market.address,
+        proposal: proposalAddress(market),
+        disputer: disputer.publicKey,
+        disputerUsdcAccount: disputerUsdc,
+        bondVault: bondVaultAddress(market),
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([disputer])
+      .rpc();
+  }
+
+  function resolve(market: Market, result: number, winnerUsdcAccount: PublicKey) {
+    return program.methods
+      .resolveDispute(new anchor.BN(result), Array(32).fill(0), "")
+      .accountsPartial({
+        market: market.address,
+        proposal: proposalAddress(market),
+        arbiter: arbiter.publicKey,
+        winnerUsdcAccount,
+        bondVault: bondVaultAddress(market),
+        usdcLiquidityAccount: market.vault,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([arbiter])
+      .rpc();
+  }
+
+  it("Leaves the authority no way to settle an oracle market", async () => {
+    const { market, settlementAvailableTs } = await oracleMarket();
+
+    await waitUntil(provider, settlementAvailableTs);
+    await expectError(settle(program, market, 120_000_000_000), "OracleBound");
+  });
+
+  it("Settles on an undisputed proposal and returns the bond", async () => {
+    const { market, settlementAvailableTs } = await oracleMarket();
+    const usdcBefore = await tokenBalance(provider, proposerUsdc);
+
+    const disputeEnds = await propose(market, settlementAvailableTs, 120_000_000_000);
+    expect(await tokenBalance(provider, proposerUsdc)).to.equal(usdcBefore - BOND);
+
+    await waitUntil(provider, disputeEnds);
+    await program.methods
+      .finalizeResult()
+      .accountsPartial({
+        market: market.address,
+        proposal: proposalAddress(market),
+        proposerUsdcAccount: proposerUsdc,
+        bondVault: bondVaultAddress(market),
+        usdcLiquidityAccount: market.vault,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    const marketAccount = await program.account.marketState.fetch(market.address);
+    expect(marketAccount.isSettled).to.be.true;
+    expect(marketAccount.winningOutcome).to.deep.equal(YES);
+    expect(marketAccount.settlement.settler.toString()).to.equal(
+      proposer.publicKey.toString()
+    );
+    expect(await tokenBalance(provider, proposerUsdc)).to.equal(usdcBefore);
+  });
+
+  it("Awards both bonds to the proposer when the ruling pays out the same", async () => {
+    const { market, settlementAvailableTs } = await oracleMarket();
+    const usdcBefore = await tokenBalance(provider, proposerUsdc);
+
+    await propose(market, settlementAvailableTs, 120_000_000_000);
+    await dispute(market);
+    // A different amount, but still above the goal
+    await resolve(market, 150_000_000_000, proposerUsdc);
+
+    const marketAccount = await program.account.marketState.fetch(market.address);
+    expect(marketAccount.winningOutcome).to.deep.equal(YES);
+    expect(marketAccount.settlement.fundraisingResult.toNumber()).to.equal(
+      150_000_000_000
+    );
+    expect(await tokenBalance(provider, proposerUsdc)).to.equal(usdcBefore + BOND);
+    expect(await tokenBalance(provider, bondVaultAddress(market))).to.equal(0);
+  });
+
+  it("Awards both bonds to the disputer when the ruling overturns the proposal", async () => {
+    const { market, settlementAvailableTs } = await oracleMarket();
+    const usdcBefore = await tokenBalance(provider, disputerUsdc);
+
+    await propose(market, settlementAvailableTs, 120_000_000_000);
+    await dispute(market);
+    await resolve(market, 50_000_000_000, disputerUsdc);
+
+    const marketAccount = await program.account.marketState.fetch(market.address);
+    expect(marketAccount.winningOutcome).to.deep.equal(NO);
+    expect(await tokenBalance(provider, disputerUsdc)).to.equal(usdcBefore + BOND);
+    expect(await tokenBalance(provider, bondVaultAddress(market))).to.equal(0);
+  });
+
+  it("Rejects a ruling paid to the losing side's account", async () => {
+    const { market, settlementAvailableTs } = await oracleMarket();
+
+    await propose(market, settlementAvailableTs, 120_000_000_000);
+    await dispute(market);
+    await expectError(
+      resolve(market, 50_000_000_000, proposerUsdc),
+      "TokenAccountOwnerMismatch"
+    );
+  });
+
+  it("Returns the bond of a proposal pending when the market is voided", async () => {
+    const { market, settlementAvailableTs } = await oracleMarket();
+    const usdcBefore = await tokenBalance(provider, proposerUsdc);
+
+    await propose(market, settlementAvailableTs, 120_000_000_000);
+    await voidMarket(program, market);
+
+    await program.methods
+      .reclaimBond()
+      .accountsPartial({
+        market: market.address,
+        proposal: proposalAddress(market),
+        claimant: proposer.publicKey,
+        claimantUsdcAccount: proposerUsdc,
+        bondVault: bondVaultAddress(market),
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([proposer])
+      .rpc();
+
+    expect(await tokenBalance(provider, proposerUsdc)).to.equal(usdcBefore);
+  });
+});
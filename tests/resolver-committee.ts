@@ -0,0 +1,155 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { IndieStarMarket } from "../target/types/indie_star_market";
+import { Keypair, PublicKey, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  Market,
+  YES,
+  chainTime,
+  createMarket,
+  createUsdcMint,
+  ensureConfig,
+  expectError,
+  fund,
+  settle,
+  voidMarket,
+  waitUntil,
+} from "./helpers";
+
+describe("resolver committee", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace
+    .indieStarMarket as Program<IndieStarMarket>;
+
+  let authority: Keypair;
+  let resolvers: Keypair[];
+  let usdcMint: PublicKey;
+
+  before(async () => {
+    authority = Keypair.generate();
+    resolvers = [Keypair.generate(), Keypair.generate(), Keypair.generate()];
+    await fund(provider, authority, ...resolvers);
+    await ensureConfig(program);
+    usdcMint = await createUsdcMint(provider, authority);
+  });
+
+  function committeeAddress(market: Market): PublicKey {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("resolvers"), market.address.toBuffer()],
+      program.programId
+    )[0];
+  }
+
+  function createCommittee(market: Market, members: PublicKey[], threshold: number) {
+    return program.methods
+      .createResolverCommittee(members, threshold)
+      .accountsPartial({
+        market: market.address,
+        authority: authority.publicKey,
+        committee: committeeAddress(market),
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+  }
+
+  function vote(market: Market, resolver: Keypair, result: number) {
+    return program.methods
+      .submitResolution(new anchor.BN(result), Array(32).fill(0), "")
+      .accountsPartial({
+        market: market.address,
+        committee: committeeAddress(market),
+        resolver: resolver.publicKey,
+        usdcLiquidityAccount: market.vault,
+      })
+      .signers([resolver])
+      .rpc();
+  }
+
+  // A market decided 2 of 3 by `resolvers`, whose settlement opens a few
+  // seconds from now
+  async function committeeMarket(): Promise<{
+    market: Market;
+    settlementAvailableTs: number;
+  }> {
+    const settlementAvailableTs = (await chainTime(provider)) + 4;
+    const market = await createMarket(program, authority, usdcMint, {
+      tradingCloseTs: new anchor.BN(settlementAvailableTs),
+    });
+    await createCommittee(
+      market,
+      resolvers.map((resolver) => resolver.publicKey),
+      2
+    );
+    return { market, settlementAvailableTs };
+  }
+
+  it("Rejects a committee listing a resolver twice", async () => {
+    const market = await createMarket(program, authority, usdcMint);
+
+    await expectError(
+      createCommittee(
+        market,
+        [resolvers[0].publicKey, resolvers[1].publicKey, resolvers[0].publicKey],
+        2
+      ),
+      "InvalidResolverCommittee"
+    );
+  });
+
+  it("Settles once the threshold agrees, even on different amounts", async () => {
+    const { market, settlementAvailableTs } = await committeeMarket();
+
+    await waitUntil(provider, settlementAvailableTs);
+    await vote(market, resolvers[0], 120_000_000_000);
+    let marketAccount = await program.account.marketState.fetch(market.address);
+    expect(marketAccount.isSettled).to.be.false;
+
+    // Also above the goal, so it pays out the same way
+    await vote(market, resolvers[1], 150_000_000_000);
+    marketAccount = await program.account.marketState.fetch(market.address);
+    expect(marketAccount.isSettled).to.be.true;
+    expect(marketAccount.winningOutcome).to.deep.equal(YES);
+    expect(marketAccount.settlement.fundraisingResult.toNumber()).to.equal(
+      150_000_000_000
+    );
+    expect(marketAccount.settlement.settler.toString()).to.equal(
+      resolvers[1].publicKey.toString()
+    );
+  });
+
+  it("Counts each resolver's vote once", async () => {
+    const { market, settlementAvailableTs } = await committeeMarket();
+
+    await waitUntil(provider, settlementAvailableTs);
+    await vote(market, resolvers[0], 120_000_000_000);
+    await expectError(vote(market, resolvers[0], 120_000_000_000), "AlreadyVoted");
+  });
+
+  it("Blocks the authority's settlement paths", async () => {
+    const { market, settlementAvailableTs } = await committeeMarket();
+
+    await waitUntil(provider, settlementAvailableTs);
+    await expectError(
+      settle(program, market, 120_000_000_000),
+      "ResolverCommitteeRequired"
+    );
+    await expectError(
+      program.methods
+        .earlySettle()
+        .accountsPartial({
+          market: market.address,
+          source: market.address,
+          caller: authority.publicKey,
+          usdcLiquidityAccount: market.vault,
+        })
+        .signers([authority])
+        .rpc(),
+      "ResolverCommitteeRequired"
+    );
+    await expectError(voidMarket(program, market), "ResolutionDeadlineNotPassed");
+  });
+});
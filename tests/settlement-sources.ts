@@ -0,0 +1,435 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { IndieStarMarket } from "../target/types/indie_star_market";
+import { MockCampaign } from "../target/types/mock_campaign";
+import { MockFundraisingFeed } from "../target/types/mock_fundraising_feed";
+import { Keypair, PublicKey, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  Market,
+  NO,
+  YES,
+  chainTime,
+  createMarket,
+  createUsdcMint,
+  ensureConfig,
+  expectError,
+  fund,
+  settle,
+  waitUntil,
+} from "./helpers";
+
+// Layout of the mock feed account, mirroring FundraisingFeed's offsets
+const FEED_LAYOUT = {
+  publisherOffset: 8,
+  valueOffset: 40,
+  timestampOffset: 48,
+};
+
+describe("settlement sources", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace
+    .indieStarMarket as Program<IndieStarMarket>;
+  const feedProgram = anchor.workspace
+    .mockFundraisingFeed as Program<MockFundraisingFeed>;
+  const campaignProgram = anchor.workspace
+    .mockCampaign as Program<MockCampaign>;
+  const publisher = provider.wallet.publicKey;
+
+  let authority: Keypair;
+  let usdcMint: PublicKey;
+
+  before(async () => {
+    authority = Keypair.generate();
+    await fund(provider, authority);
+    await ensureConfig(program);
+    usdcMint = await createUsdcMint(provider, authority);
+  });
+
+  // Settle `market` early on what `source` reports
+  function earlySettle(market: Market, source: PublicKey) {
+    return program.methods
+      .earlySettle()
+      .accountsPartial({
+        market: market.address,
+        source,
+        caller: publisher,
+        usdcLiquidityAccount: market.vault,
+      })
+      .rpc();
+  }
+
+  describe("settle_from_feed", () => {
+    // A feed published by the provider wallet
+    async function createFeed(totalRaised: number): Promise<PublicKey> {
+      const feed = Keypair.generate();
+      await feedProgram.methods
+        .initializeFeed(new anchor.BN(totalRaised))
+        .accountsPartial({
+          feed: feed.publicKey,
+          publisher,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([feed])
+        .rpc();
+      return feed.publicKey;
+    }
+
+    // Report `totalRaised`, stamped with the current time unless `updatedAt` is given
+    async function setFeed(
+      feed: PublicKey,
+      totalRaised: number,
+      updatedAt?: number
+    ): Promise<void> {
+      if (updatedAt === undefined) {
+        await feedProgram.methods
+          .updateFeed(new anchor.BN(totalRaised))
+          .accountsPartial({ feed, publisher })
+          .rpc();
+      } else {
+        await feedProgram.methods
+          .setFeed(new anchor.BN(totalRaised), new anchor.BN(updatedAt))
+          .accountsPartial({ feed, publisher })
+          .rpc();
+      }
+    }
+
+    // A market reading `feed`, whose campaign ends and settlement opens a few
+    // seconds from now; `config` overrides the feed config
+    async function feedMarket(
+      feed: PublicKey,
+      config: Record<string, any> = {}
+    ): Promise<{ market: Market; campaignEndTs: number }> {
+      const campaignEndTs = (await chainTime(provider)) + 3;
+      const market = await createMarket(program, authority, usdcMint, {
+        tradingCloseTs: new anchor.BN(campaignEndTs),
+        fundraisingFeed: {
+          feed,
+          ownerProgram: feedProgram.programId,
+          publisher,
+          ...FEED_LAYOUT,
+          maxStaleness: new anchor.BN(3600),
+          ...config,
+        },
+      });
+      return { market, campaignEndTs };
+    }
+
+    function settleFromFeed(market: Market, feed: PublicKey) {
+      return program.methods
+        .settleFromFeed()
+        .accountsPartial({
+          market: market.address,
+          feed,
+          caller: publisher,
+          usdcLiquidityAccount: market.vault,
+        })
+        .rpc();
+    }
+
+    it("Settles on a fresh reading taken after the campaign ended", async () => {
+      const feed = await createFeed(0);
+      const { market, campaignEndTs } = await feedMarket(feed);
+
+      await waitUntil(provider, campaignEndTs);
+      await setFeed(feed, 120_000_000_000);
+      await settleFromFeed(market, feed);
+
+      const marketAccount = await program.account.marketState.fetch(
+        market.address
+      );
+      expect(marketAccount.isSettled).to.be.true;
+      expect(marketAccount.winningOutcome).to.deep.equal(YES);
+      expect(marketAccount.settlement.fundraisingResult.toNumber()).to.equal(
+        120_000_000_000
+      );
+      expect(marketAccount.settlement.settler.toString()).to.equal(
+        publisher.toString()
+      );
+    });
+
+    it("Rejects a reading older than max_staleness", async () => {
+      const feed = await createFeed(0);
+      const { market, campaignEndTs } = await feedMarket(feed, {
+        maxStaleness: new anchor.BN(1),
+      });
+
+      await waitUntil(provider, campaignEndTs + 2);
+      // Taken after the campaign ended, but more than a second ago
+      await setFeed(feed, 120_000_000_000, campaignEndTs);
+      await expectError(settleFromFeed(market, feed), "StaleFeed");
+    });
+
+    it("Rejects a reading from before the campaign ended", async () => {
+      const feed = await createFeed(0);
+      const { market, campaignEndTs } = await feedMarket(feed);
+
+      await waitUntil(provider, campaignEndTs);
+      await setFeed(feed, 120_000_000_000, campaignEndTs - 1);
+      await expectError(settleFromFeed(market, feed), "StaleFeed");
+    });
+
+    it("Rejects a feed signed by another publisher", async () => {
+      const feed = await createFeed(0);
+      const { market, campaignEndTs } = await feedMarket(feed, {
+        publisher: Keypair.generate().publicKey,
+      });
+
+      await waitUntil(provider, campaignEndTs);
+      await setFeed(feed, 120_000_000_000);
+      await expectError(settleFromFeed(market, feed), "FeedPublisherMismatch");
+    });
+
+    it("Rejects a feed owned by another program", async () => {
+      const feed = await createFeed(0);
+      const { market, campaignEndTs } = await feedMarket(feed, {
+        ownerProgram: program.programId,
+      });
+
+      await waitUntil(provider, campaignEndTs);
+      await setFeed(feed, 120_000_000_000);
+      await expectError(settleFromFeed(market, feed), "FeedMismatch");
+    });
+
+    it("Leaves the authority no way to settle a feed market", async () => {
+      const feed = await createFeed(0);
+      const { market, campaignEndTs } = await feedMarket(feed);
+
+      await waitUntil(provider, campaignEndTs);
+      await expectError(settle(program, market, 120_000_000_000), "FeedBound");
+    });
+
+    it("Settles early as soon as the feed reports the goal met", async () => {
+      const feed = await createFeed(0);
+      const { market } = await feedMarket(feed);
+
+      await setFeed(feed, 120_000_000_000);
+      await earlySettle(market, feed);
+
+      const marketAccount = await program.account.marketState.fetch(
+        market.address
+      );
+      expect(marketAccount.isSettled).to.be.true;
+      expect(marketAccount.winningOutcome).to.deep.equal(YES);
+      expect(marketAccount.settlement.fundraisingResult.toNumber()).to.equal(
+        120_000_000_000
+      );
+    });
+
+    it("Won't settle early while the feed is below the goal", async () => {
+      const feed = await createFeed(0);
+      const { market } = await feedMarket(feed);
+
+      await setFeed(feed, 50_000_000_000);
+      await expectError(earlySettle(market, feed), "GoalNotReached");
+    });
+
+    it("Won't settle early on an account other than the market's feed", async () => {
+      const feed = await createFeed(0);
+      const other = await createFeed(120_000_000_000);
+      const { market } = await feedMarket(feed);
+
+      await expectError(earlySettle(market, other), "UntrustedSource");
+    });
+  });
+
+  describe("settle_from_campaign", () => {
+    const goal = 100_000_000_000;
+    const creator = provider.wallet.publicKey;
+
+    async function createCampaign(endTs: number): Promise<PublicKey> {
+      const campaign = Keypair.generate();
+      await campaignProgram.methods
+        .createCampaign(new anchor.BN(goal), new anchor.BN(endTs))
+        .accountsPartial({
+          campaign: campaign.publicKey,
+          creator,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([campaign])
+        .rpc();
+      return campaign.publicKey;
+    }
+
+    async function finishCampaign(
+      campaign: PublicKey,
+      totalRaised: number,
+      status: Record<string, object>
+    ): Promise<void> {
+      await campaignProgram.methods
+        .setRaised(new anchor.BN(totalRaised))
+        .accountsPartial({ campaign, creator })
+        .rpc();
+      await campaignProgram.methods
+        .setStatus(status as any)
+        .accountsPartial({ campaign, creator })
+        .rpc();
+    }
+
+    // A market bound to a new campaign that ends a few seconds from now
+    async function campaignMarket(): Promise<{
+      market: Market;
+      campaign: PublicKey;
+      campaignEndTs: number;
+    }> {
+      const campaignEndTs = (await chainTime(provider)) + 3;
+      const campaign = await createCampaign(campaignEndTs);
+      const market = await createMarket(
+        program,
+        authority,
+        usdcMint,
+        {
+          fundraisingGoal: new anchor.BN(goal),
+          tradingCloseTs: new anchor.BN(campaignEndTs),
+          campaignProgram: campaignProgram.programId,
+        },
+        campaign
+      );
+      return { market, campaign, campaignEndTs };
+    }
+
+    function settleFromCampaign(market: Market, campaign: PublicKey) {
+      return program.methods
+        .settleFromCampaign()
+        .accountsPartial({
+          market: market.address,
+          campaign,
+          caller: creator,
+          usdcLiquidityAccount: market.vault,
+        })
+        .rpc();
+    }
+
+    it("Rejects a market whose goal differs from the campaign's", async () => {
+      const campaignEndTs = (await chainTime(provider)) + 3600;
+      const campaign = await createCampaign(campaignEndTs);
+
+      await expectError(
+        createMarket(
+          program,
+          authority,
+          usdcMint,
+          {
+            fundraisingGoal: new anchor.BN(goal + 1),
+            tradingCloseTs: new anchor.BN(campaignEndTs),
+            campaignProgram: campaignProgram.programId,
+          },
+          campaign
+        ),
+        "CampaignGoalMismatch"
+      );
+    });
+
+    it("Rejects a market whose campaign end differs from the campaign's", async () => {
+      const campaignEndTs = (await chainTime(provider)) + 3600;
+      const campaign = await createCampaign(campaignEndTs);
+
+      await expectError(
+        createMarket(
+          program,
+          authority,
+          usdcMint,
+          {
+            fundraisingGoal: new anchor.BN(goal),
+            tradingCloseTs: new anchor.BN(campaignEndTs - 60),
+            campaignEndTs: new anchor.BN(campaignEndTs - 60),
+            campaignProgram: campaignProgram.programId,
+          },
+          campaign
+        ),
+        "CampaignEndMismatch"
+      );
+    });
+
+    it("Won't settle while the campaign is still active", async () => {
+      const { market, campaign, campaignEndTs } = await campaignMarket();
+
+      await waitUntil(provider, campaignEndTs);
+      await campaignProgram.methods
+        .setRaised(new anchor.BN(goal))
+        .accountsPartial({ campaign, creator })
+        .rpc();
+      await expectError(
+        settleFromCampaign(market, campaign),
+        "CampaignNotFinished"
+      );
+    });
+
+    it("Won't settle a cancelled campaign's market", async () => {
+      const { market, campaign, campaignEndTs } = await campaignMarket();
+
+      await waitUntil(provider, campaignEndTs);
+      await finishCampaign(campaign, goal, { cancelled: {} });
+      await expectError(
+        settleFromCampaign(market, campaign),
+        "CampaignCancelled"
+      );
+    });
+
+    it("Settles YES on the amount a succeeded campaign raised", async () => {
+      const { market, campaign, campaignEndTs } = await campaignMarket();
+
+      await waitUntil(provider, campaignEndTs);
+      await finishCampaign(campaign, 120_000_000_000, { succeeded: {} });
+      await settleFromCampaign(market, campaign);
+
+      const marketAccount = await program.account.marketState.fetch(
+        market.address
+      );
+      expect(marketAccount.isSettled).to.be.true;
+      expect(marketAccount.winningOutcome).to.deep.equal(YES);
+      expect(marketAccount.settlement.fundraisingResult.toNumber()).to.equal(
+        120_000_000_000
+      );
+    });
+
+    it("Settles NO on the amount a failed campaign raised", async () => {
+      const { market, campaign, campaignEndTs } = await campaignMarket();
+
+      await waitUntil(provider, campaignEndTs);
+      await finishCampaign(campaign, 40_000_000_000, { failed: {} });
+      await settleFromCampaign(market, campaign);
+
+      const marketAccount = await program.account.marketState.fetch(
+        market.address
+      );
+      expect(marketAccount.isSettled).to.be.true;
+      expect(marketAccount.winningOutcome).to.deep.equal(NO);
+      expect(marketAccount.settlement.fundraisingResult.toNumber()).to.equal(
+        40_000_000_000
+      );
+    });
+
+    it("Leaves the authority no way to settle a campaign-bound market", async () => {
+      const { market, campaignEndTs } = await campaignMarket();
+
+      await waitUntil(provider, campaignEndTs);
+      await expectError(settle(program, market, 120_000_000_000), "CampaignBound");
+    });
+
+    it("Settles early once the active campaign reports the goal met", async () => {
+      const { market, campaign } = await campaignMarket();
+
+      await campaignProgram.methods
+        .setRaised(new anchor.BN(goal))
+        .accountsPartial({ campaign, creator })
+        .rpc();
+      await earlySettle(market, campaign);
+
+      const marketAccount = await program.account.marketState.fetch(
+        market.address
+      );
+      expect(marketAccount.isSettled).to.be.true;
+      expect(marketAccount.winningOutcome).to.deep.equal(YES);
+    });
+
+    it("Won't settle early on a campaign the market isn't bound to", async () => {
+      const { market } = await campaignMarket();
+      const other = await createCampaign((await chainTime(provider)) + 3600);
+
+      await expectError(earlySettle(market, other), "UntrustedSource");
+    });
+  });
+});